@@ -0,0 +1,281 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub trait Variables {
+    fn get(&self, name: &str) -> Option<String>;
+    fn set(&mut self, name: &str, value: String);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+}
+
+enum Node {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Assign(String, &'static str, Box<Node>),
+    Increment(String, i64, bool),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+}
+
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "=",
+    "?", ":", ",",
+];
+
+pub fn evaluate(expression: &str, variables: &mut dyn Variables) -> Result<i64, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = ArithmeticParser { tokens, position: 0 };
+    let node = parser.parse_expr(0)?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        return Err(format!("Unexpected {:?} in arithmetic expression: {}", token, expression));
+    }
+    eval(&node, variables)
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            tokens.push(Token::Number(read_number(&mut chars)?));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            if c == '$' {
+                chars.next();
+            }
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::LeftParen } else { Token::RightParen });
+        } else {
+            let rest = chars.clone().collect::<String>();
+            let operator = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or(format!("Invalid character {} in arithmetic expression", c))?;
+            operator.chars().for_each(|_| { chars.next(); });
+            tokens.push(Token::Operator(operator));
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_number(chars: &mut Peekable<Chars>) -> Result<i64, String> {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_alphanumeric() {
+            break;
+        }
+        digits.push(c);
+        chars.next();
+    }
+    let parsed = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None if digits.len() > 1 && digits.starts_with('0') => i64::from_str_radix(&digits[1..], 8),
+        None => digits.parse::<i64>(),
+    };
+    parsed.map_err(|_| format!("{} is not a valid number", digits))
+}
+
+struct ArithmeticParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl ArithmeticParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn get_binding_power(op: &str) -> Option<(u8, u8)> {
+        match op {
+            "," => Some((1, 2)),
+            "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "<<=" | ">>=" | "&=" | "|=" | "^=" => Some((4, 3)),
+            "?" => Some((6, 5)),
+            "||" => Some((7, 8)),
+            "&&" => Some((9, 10)),
+            "|" => Some((11, 12)),
+            "^" => Some((13, 14)),
+            "&" => Some((15, 16)),
+            "==" | "!=" => Some((17, 18)),
+            "<" | "<=" | ">" | ">=" => Some((19, 20)),
+            "<<" | ">>" => Some((21, 22)),
+            "+" | "-" => Some((23, 24)),
+            "*" | "/" | "%" => Some((25, 26)),
+            "**" => Some((28, 27)),
+            _ => None,
+        }
+    }
+
+    fn parse_expr(&mut self, min_binding_power: u8) -> Result<Node, String> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Operator(op)) = self.peek() {
+            let op = *op;
+            let (left_bp, right_bp) = match Self::get_binding_power(op) {
+                Some(bp) => bp,
+                None => break,
+            };
+            if min_binding_power > left_bp {
+                break;
+            }
+            self.next();
+
+            lhs = match op {
+                "?" => {
+                    let then_node = self.parse_expr(0)?;
+                    if self.next() != Some(Token::Operator(":")) {
+                        return Err("Expected : in conditional expression".to_string());
+                    }
+                    let else_node = self.parse_expr(right_bp)?;
+                    Node::Conditional(Box::new(lhs), Box::new(then_node), Box::new(else_node))
+                }
+                _ if right_bp < left_bp && op != "**" => match lhs {
+                    Node::Variable(name) => Node::Assign(name, op, Box::new(self.parse_expr(right_bp)?)),
+                    _ => return Err(format!("Expected a variable before {}", op)),
+                },
+                _ => Node::Binary(op, Box::new(lhs), Box::new(self.parse_expr(right_bp)?)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Node::Number(number)),
+            Some(Token::Name(name)) => match self.peek() {
+                Some(Token::Operator(op @ ("++" | "--"))) => {
+                    let delta = if *op == "++" { 1 } else { -1 };
+                    self.next();
+                    Ok(Node::Increment(name, delta, false))
+                }
+                _ => Ok(Node::Variable(name)),
+            },
+            Some(Token::Operator(op @ ("++" | "--"))) => match self.next() {
+                Some(Token::Name(name)) => Ok(Node::Increment(name, if op == "++" { 1 } else { -1 }, true)),
+                _ => Err(format!("Expected a variable after {}", op)),
+            },
+            Some(Token::Operator(op @ ("-" | "+" | "!" | "~"))) => {
+                Ok(Node::Unary(op, Box::new(self.parse_expr(27)?)))
+            }
+            Some(Token::LeftParen) => {
+                let node = self.parse_expr(0)?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(node),
+                    _ => Err("Expected ) in arithmetic expression".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?} in arithmetic expression", token)),
+            None => Err("Unexpected end of arithmetic expression".to_string()),
+        }
+    }
+}
+
+fn get_variable(name: &str, variables: &dyn Variables) -> Result<i64, String> {
+    match variables.get(name) {
+        Some(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| format!("{} is not a valid number in variable {}", value, name)),
+        _ => Ok(0),
+    }
+}
+
+fn eval(node: &Node, variables: &mut dyn Variables) -> Result<i64, String> {
+    match node {
+        Node::Number(number) => Ok(*number),
+        Node::Variable(name) => get_variable(name, variables),
+        Node::Unary(op, operand) => {
+            let value = eval(operand, variables)?;
+            Ok(match *op {
+                "-" => value.wrapping_neg(),
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                _ => value,
+            })
+        }
+        Node::Binary("&&", lhs, rhs) => {
+            Ok((eval(lhs, variables)? != 0 && eval(rhs, variables)? != 0) as i64)
+        }
+        Node::Binary("||", lhs, rhs) => {
+            Ok((eval(lhs, variables)? != 0 || eval(rhs, variables)? != 0) as i64)
+        }
+        Node::Binary(",", lhs, rhs) => {
+            eval(lhs, variables)?;
+            eval(rhs, variables)
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let left = eval(lhs, variables)?;
+            let right = eval(rhs, variables)?;
+            apply(op, left, right)
+        }
+        Node::Assign(name, op, value) => {
+            let value = eval(value, variables)?;
+            let result = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                Some(op) => apply(op, get_variable(name, variables)?, value)?,
+                None => value,
+            };
+            variables.set(name, result.to_string());
+            Ok(result)
+        }
+        Node::Increment(name, delta, prefix) => {
+            let old = get_variable(name, variables)?;
+            variables.set(name, (old + delta).to_string());
+            Ok(if *prefix { old + delta } else { old })
+        }
+        Node::Conditional(cond, then_node, else_node) => {
+            if eval(cond, variables)? != 0 { eval(then_node, variables) } else { eval(else_node, variables) }
+        }
+    }
+}
+
+fn apply(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("Division by zero".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => return Err("Exponent less than 0".to_string()),
+        "**" => left.wrapping_pow(right as u32),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "|" => left | right,
+        "^" => left ^ right,
+        _ => return Err(format!("Unknown operator {}", op)),
+    })
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operator {
     Pipe,
     Next,
@@ -7,7 +7,7 @@ pub enum Operator {
     LogicOr,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expr {
     Cmd {
         name: String,
@@ -15,11 +15,46 @@ pub enum Expr {
         redirect: Redirect,
     },
     Binary(Box<Expr>, Operator, Box<Expr>),
+    Subshell(Box<Expr>, Redirect),
+    Group(Box<Expr>, Redirect),
     If(Box<Expr>, Box<Expr>),
     IfElse(Box<Expr>, Box<Expr>, Box<Expr>),
+    While(Box<Expr>, Box<Expr>),
+    Until(Box<Expr>, Box<Expr>),
+    For(String, Option<Vec<String>>, Box<Expr>),
+    ArithmeticFor(String, String, String, Box<Expr>),
+    Arithmetic(String),
+    Case(String, Vec<CaseArm>),
+    Function(String, Box<Expr>),
+    Not(Box<Expr>),
+    Time(Box<Expr>, bool),
+    Conditional(Condition),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Unary(String, String),
+    Binary(String, String, String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseTerminator {
+    Break,
+    FallThrough,
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseArm {
+    pub patterns: Vec<String>,
+    pub body: Option<Expr>,
+    pub terminator: CaseTerminator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Redirect {
     pub stdin: Option<String>,
     pub stdout: Option<String>,
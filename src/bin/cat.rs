@@ -1,12 +1,26 @@
 #![allow(warnings)]
 
-use shell::utils::{get_args, process_options, read_files};
+use shell::utils::{get_args, process_options, read_files, OptionSpec, ProgramSpec};
+
+const SPEC: ProgramSpec = ProgramSpec {
+    name: "cat",
+    usage: "[OPTION]... [FILE]...",
+    about: "Concatenate files to the standard output.",
+    options: &[
+        OptionSpec { short: 'b', help: "Omit empty lines" },
+        OptionSpec { short: 'n', help: "Number all output lines" },
+    ],
+};
 
 fn main() {
     let mut stdout = String::new();
     let mut stderr = String::new();
 
     let arguments = get_args();
+    if let Some(text) = SPEC.standard_option(&arguments) {
+        print!("{}", text);
+        return;
+    }
     let (files, options) = split_args(&arguments);
 
 
@@ -14,7 +28,7 @@ fn main() {
         read_files(files, &mut stdout, &mut stderr)
     } else {}
 
-    match process_options(options, vec!['b', 'n']) {
+    match process_options(options, SPEC.short_options()) {
         Ok(flags) => {
             if flags.contains(&'b') {
                 stdout = remove_empty_lines(&stdout)
@@ -1,8 +1,19 @@
 use std::process::exit;
-use shell::utils::{eq, get_args};
+use shell::utils::{eq, get_args, ProgramSpec};
+
+const SPEC: ProgramSpec = ProgramSpec {
+    name: "eq",
+    usage: "STRING STRING...",
+    about: "Succeed if all strings are equal, or exit with 2 given fewer than two strings.",
+    options: &[],
+};
 
 fn main() {
     let arguments = get_args();
+    if let Some(text) = SPEC.standard_option(&arguments) {
+        print!("{}", text);
+        exit(0)
+    }
     if arguments.len() < 2 {
         exit(2)
     }
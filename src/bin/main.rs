@@ -1,34 +1,100 @@
-use std::env;
+use std::fs::OpenOptions;
 use std::io::{stdin, stdout, Write};
+use std::path::Path;
+use shell::config::{Config, RC_FILE_NAME, SYSTEM_RC_FILE};
 use shell::interpreter::Interpreter;
-use shell::utils::{get_program_dir, parse};
+use shell::utils::{get_args, get_program_dir, home_dir, is_incomplete, join_program_dirs, parse_with_aliases};
+
+const IGNORED_EOF_LIMIT: usize = 10;
 
 fn main() {
-    let program_dir = get_program_dir();
-    let mut interpreter = Interpreter::new(&program_dir);
+    let config = Config::load(&get_args()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        Config::default()
+    });
+    let program_dir = match config.programs.is_empty() {
+        true => get_program_dir(),
+        false => join_program_dirs(&config.programs),
+    };
+    let mut interpreter = Interpreter::new(&program_dir).with_config(&config);
+    let mut ignored_eof = 0;
+    for rc_file in get_rc_files(&get_args()) {
+        let (stderr, stdout) = interpreter.eval_file(&rc_file);
+        stderr.iter().for_each(|x| eprintln!("{}", x));
+        stdout.iter().for_each(|x| println!("{}", x));
+    }
     loop {
-        display_prompt();
-        let input = read_input();
-        let ast = parse(&input, &program_dir);
+        interpreter.run_prompt_command();
+        display_prompt(&interpreter.prompt("PS1"));
+        let mut input = match read_input() {
+            Some(input) => input,
+            None if interpreter.option("ignoreeof") && ignored_eof < IGNORED_EOF_LIMIT => {
+                ignored_eof += 1;
+                println!("\nUse \"exit\" to leave the shell.");
+                continue;
+            }
+            None => interpreter.exit(),
+        };
+        ignored_eof = 0;
+        if input.trim().is_empty() {
+            continue;
+        }
+        while is_incomplete(&input, &program_dir, interpreter.aliases()) {
+            display_prompt(&interpreter.prompt("PS2"));
+            match read_input() {
+                Some(line) => input.push_str(&line),
+                None => break,
+            }
+        }
+        if interpreter.option("verbose") {
+            eprint!("{}", input);
+        }
+        save_history(&config, &input);
+        let ast = parse_with_aliases(&input, &program_dir, interpreter.aliases());
         match &ast {
             Ok(expr) => {
                 let (stderr, stdout) = interpreter.eval(expr);
                 stderr.iter().for_each(|x| eprintln!("{}", x));
                 stdout.iter().for_each(|x| println!("{}", x));
             }
-            Err(err) => eprintln!("{}", err)
+            Err(err) => eprintln!("{}", err.render(&input))
         };
     }
 }
 
-fn display_prompt() {
-    let cwd = env::current_dir().unwrap().display().to_string();
-    print!("{}> ", cwd);
+fn get_rc_files(args: &[String]) -> Vec<String> {
+    if args.iter().any(|arg| arg == "--norc") {
+        return vec![];
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--rcfile") {
+        return args.get(position + 1).cloned().into_iter().collect();
+    }
+
+    let mut rc_files = vec![SYSTEM_RC_FILE.to_string()];
+    if let Some(home) = home_dir() {
+        rc_files.push(Path::new(&home).join(RC_FILE_NAME).display().to_string());
+    }
+    rc_files.into_iter().filter(|rc_file| Path::new(rc_file).is_file()).collect()
+}
+
+fn save_history(config: &Config, input: &str) {
+    let Some(path) = &config.history_path else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", input.trim_end_matches('\n'));
+    }
+}
+
+fn display_prompt(prompt: &str) {
+    print!("{}", prompt);
     stdout().flush().expect("Could not flush stdout")
 }
 
-fn read_input() -> String {
+fn read_input() -> Option<String> {
     let mut input = String::new();
-    stdin().read_line(&mut input).expect("Could not read input");
-    input.trim_start().to_string()
+    match stdin().read_line(&mut input).expect("Could not read input") {
+        0 => None,
+        _ => Some(input.trim_start().to_string())
+    }
 }
\ No newline at end of file
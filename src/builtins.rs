@@ -0,0 +1,166 @@
+use std::io::{Read, Write};
+
+use crate::help;
+use crate::interpreter::Interpreter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl ExitStatus {
+    pub const SUCCESS: ExitStatus = ExitStatus(0);
+    pub const FAILURE: ExitStatus = ExitStatus(1);
+
+    pub fn success(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<bool> for ExitStatus {
+    fn from(success: bool) -> ExitStatus {
+        if success { ExitStatus::SUCCESS } else { ExitStatus::FAILURE }
+    }
+}
+
+pub trait Builtin {
+    fn name(&self) -> &str;
+
+    fn help(&self) -> &str;
+
+    fn run(
+        &self,
+        context: &mut Interpreter,
+        arguments: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> ExitStatus;
+}
+
+impl<B: Builtin + ?Sized> Builtin for Box<B> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn help(&self) -> &str {
+        (**self).help()
+    }
+
+    fn run(
+        &self,
+        context: &mut Interpreter,
+        arguments: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> ExitStatus {
+        (**self).run(context, arguments, stdin, stdout, stderr)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Internal {
+    name: &'static str,
+    help: &'static str,
+    run: Run,
+}
+
+#[derive(Clone, Copy)]
+enum Run {
+    Success(fn(&mut Interpreter, &[String], &mut Streams) -> bool),
+    Status(fn(&mut Interpreter, &[String], &mut Streams) -> i32),
+}
+
+pub struct Streams<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+impl Streams<'_> {
+    pub fn print(&mut self, text: &str) {
+        self.write(text.as_bytes());
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        let _ = self.stdout.write_all(bytes);
+    }
+}
+
+impl Builtin for Internal {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn help(&self) -> &str {
+        self.help
+    }
+
+    fn run(
+        &self,
+        context: &mut Interpreter,
+        arguments: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> ExitStatus {
+        let mut streams = Streams { stdin, stdout, stderr };
+        match self.run {
+            Run::Success(run) => ExitStatus::from(run(context, arguments, &mut streams)),
+            Run::Status(run) => ExitStatus(run(context, arguments, &mut streams)),
+        }
+    }
+}
+
+const fn internal(name: &'static str, help: &'static str, run: fn(&mut Interpreter, &[String], &mut Streams) -> bool) -> Internal {
+    Internal { name, help, run: Run::Success(run) }
+}
+
+const fn internal_status(name: &'static str, help: &'static str, run: fn(&mut Interpreter, &[String], &mut Streams) -> i32) -> Internal {
+    Internal { name, help, run: Run::Status(run) }
+}
+
+pub const INTERNAL: &[Internal] = &[
+    internal("cd", "cd [-L|-P] [dir]\n    Change the working directory to dir, HOME by default or OLDPWD for -.", Interpreter::cd),
+    internal_status("exit", "exit [n]\n    Exit the shell with status n, or the status of the last command.", Interpreter::exit_builtin),
+    internal("set", "set [-eunvx] [-o name] [--] [arg ...]\n    Change shell options or positional parameters, or list variables.", Interpreter::set),
+    internal("clear", "clear [-x]\n    Clear the terminal screen, keeping the scrollback with -x.", Interpreter::clear),
+    internal("trap", "trap [-p] [command | -] [signal ...]\n    Run command when the shell receives one of the signals, or reset the signals with -.", Interpreter::trap),
+    internal("break", "break [n]\n    Exit from the n innermost enclosing loops.", Interpreter::break_builtin),
+    internal("continue", "continue [n]\n    Resume the next iteration of the n-th enclosing loop.", Interpreter::continue_builtin),
+    internal("local", "local name[=value] ...\n    Declare variables local to the current function.", Interpreter::local),
+    internal_status("return", "return [n]\n    Return from a function or sourced file with status n.", Interpreter::return_function),
+    internal("test", "test expression\n    Evaluate a conditional expression.", Interpreter::test),
+    internal("[", "[ expression ]\n    Evaluate a conditional expression, like test.", Interpreter::bracket_test),
+    internal("alias", "alias [-p] [name[=value] ...]\n    Define or display aliases.", Interpreter::alias),
+    internal("unalias", "unalias [-a] name ...\n    Remove aliases.", Interpreter::unalias),
+    internal_status("source", "source file [arg ...]\n    Execute commands from file in the current shell.", Interpreter::source),
+    internal_status(".", ". file [arg ...]\n    Execute commands from file in the current shell, like source.", Interpreter::source),
+    internal("pwd", "pwd [-L|-P]\n    Print the current working directory.", Interpreter::pwd),
+    internal("echo", "echo [-neE] [arg ...]\n    Write arguments to the standard output.", Interpreter::echo),
+    internal("printf", "printf [-v var] format [arg ...]\n    Write formatted arguments to the standard output.", Interpreter::printf),
+    internal("true", "true\n    Return a successful status.", succeed),
+    internal("false", "false\n    Return an unsuccessful status.", fail),
+    internal(":", ":\n    Do nothing and return a successful status.", succeed),
+    internal("type", "type [-t] name ...\n    Describe how each name would be interpreted as a command.", Interpreter::type_builtin),
+    internal_status("command", "command [-vV] name [arg ...]\n    Run or describe a command, bypassing shell functions.", Interpreter::command),
+    internal_status("builtin", "builtin name [arg ...]\n    Run a shell builtin, bypassing shell functions.", Interpreter::builtin),
+    internal("pushd", "pushd [dir | +N | -N]\n    Push a directory onto the directory stack and change to it.", Interpreter::pushd),
+    internal("popd", "popd [+N | -N]\n    Remove a directory from the directory stack.", Interpreter::popd),
+    internal("dirs", "dirs [-clpv]\n    Display the directory stack.", Interpreter::dirs),
+    internal("reset", "reset\n    Restore the terminal to a sane state and clear it.", Interpreter::reset),
+    internal("help", "help [topic ...]\n    Describe the shell grammar and builtins, or list the topics.", help::help),
+    internal("export", "export [-p] name[=value] ...\n    Export variables to the environment of executed commands.", Interpreter::export),
+    internal("unset", "unset [-fv] name ...\n    Remove variables or functions.", Interpreter::unset),
+];
+
+pub fn is_internal(name: &str) -> bool {
+    INTERNAL.iter().any(|builtin| builtin.name == name)
+}
+
+fn succeed(_: &mut Interpreter, _: &[String], _: &mut Streams) -> bool {
+    true
+}
+
+fn fail(_: &mut Interpreter, _: &[String], _: &mut Streams) -> bool {
+    false
+}
@@ -0,0 +1,244 @@
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::ast::Condition;
+
+const UNARY_OPERATORS: &[&str] = &["-e", "-f", "-d", "-r", "-w", "-x", "-s", "-L", "-h", "-z", "-n"];
+const BINARY_OPERATORS: &[&str] = &[
+    "=", "==", "!=", "<", ">", "=~", "-eq", "-ne", "-lt", "-le", "-gt", "-ge",
+];
+
+pub fn parse(text: &str) -> Result<Condition, String> {
+    let words = tokenize(text)?;
+    ConditionParser { words, position: 0, and: "&&", or: "||" }.parse()
+}
+
+pub fn test(arguments: &[String], base: &Path) -> Result<bool, String> {
+    if arguments.is_empty() {
+        return Ok(false);
+    }
+    let words = arguments.to_vec();
+    let condition = ConditionParser { words, position: 0, and: "-a", or: "-o" }.parse()?;
+    evaluate(&condition, base)
+}
+
+pub fn evaluate(condition: &Condition, base: &Path) -> Result<bool, String> {
+    match condition {
+        Condition::Not(condition) => Ok(!evaluate(condition, base)?),
+        Condition::And(lhs, rhs) => Ok(evaluate(lhs, base)? && evaluate(rhs, base)?),
+        Condition::Or(lhs, rhs) => Ok(evaluate(lhs, base)? || evaluate(rhs, base)?),
+        Condition::Unary(op, operand) => unary(op, operand, base),
+        Condition::Binary(left, op, right) => binary(op, left, right),
+    }
+}
+
+pub fn unary(op: &str, operand: &str, base: &Path) -> Result<bool, String> {
+    let path = &base.join(operand);
+    Ok(match op {
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-r" => accessible(path, Access::Read),
+        "-w" => accessible(path, Access::Write),
+        "-x" => accessible(path, Access::Execute),
+        "-s" => fs::metadata(path).map(|metadata| metadata.len() > 0).unwrap_or(false),
+        "-L" | "-h" => path.is_symlink(),
+        "-z" => operand.is_empty(),
+        "-n" => !operand.is_empty(),
+        _ => return Err(format!("{} is not a unary operator", op)),
+    })
+}
+
+pub fn binary(op: &str, left: &str, right: &str) -> Result<bool, String> {
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+            let (left, right) = (integer(left)?, integer(right)?);
+            match op {
+                "-eq" => left == right,
+                "-ne" => left != right,
+                "-lt" => left < right,
+                "-le" => left <= right,
+                "-gt" => left > right,
+                _ => left >= right,
+            }
+        }
+        _ => return Err(format!("{} is not a binary operator", op)),
+    })
+}
+
+fn integer(value: &str) -> Result<i64, String> {
+    value.trim().parse::<i64>().map_err(|_| format!("Expected an integer but found {}", value))
+}
+
+enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+#[cfg(unix)]
+fn accessible(path: &Path, access: Access) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mode = match access {
+        Access::Read => libc::R_OK,
+        Access::Write => libc::W_OK,
+        Access::Execute => libc::X_OK,
+    };
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn accessible(path: &Path, access: Access) -> bool {
+    match (fs::metadata(path), access) {
+        (Ok(metadata), Access::Write) => !metadata.permissions().readonly(),
+        (Ok(_), _) => true,
+        (Err(_), _) => false,
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let regex = words.last().is_some_and(|word| word == "=~");
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '&' | '|' if !regex => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    return Err(format!("Unexpected {} in conditional expression", c));
+                }
+                words.push(format!("{}{}", c, c));
+            }
+            '(' | ')' if !regex => {
+                chars.next();
+                words.push(c.to_string());
+            }
+            _ => words.push(read_word(&mut chars, regex)),
+        }
+    }
+    Ok(words)
+}
+
+fn read_word(chars: &mut Peekable<Chars>, regex: bool) -> String {
+    let mut word = String::new();
+    let mut depth = 0;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\'' | '"' => {
+                chars.next();
+                word.push(c);
+                for quoted in chars.by_ref() {
+                    word.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+                continue;
+            }
+            c if c.is_whitespace() && depth == 0 => break,
+            '&' | '|' | '(' | ')' if !regex => break,
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ => (),
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+struct ConditionParser {
+    words: Vec<String>,
+    position: usize,
+    and: &'static str,
+    or: &'static str,
+}
+
+impl ConditionParser {
+    fn parse(&mut self) -> Result<Condition, String> {
+        let condition = self.parse_or()?;
+        match self.peek() {
+            None => Ok(condition),
+            Some(word) => Err(format!("Unexpected {} in conditional expression", word)),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.words.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let word = self.words.get(self.position).cloned();
+        self.position += 1;
+        word
+    }
+
+    fn binary_ahead(&self) -> bool {
+        let op = self.words.get(self.position + 1).map(String::as_str);
+        op.is_some_and(|op| BINARY_OPERATORS.contains(&op)) && self.words.len() > self.position + 2
+    }
+
+    fn parse_or(&mut self) -> Result<Condition, String> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(self.or) {
+            self.next();
+            lhs = Condition::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, String> {
+        let mut lhs = self.parse_not()?;
+        while self.peek() == Some(self.and) {
+            self.next();
+            lhs = Condition::And(Box::new(lhs), Box::new(self.parse_not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition, String> {
+        let negation = self.peek() == Some("!") && self.words.len() > self.position + 1;
+        if negation && !self.binary_ahead() {
+            self.next();
+            return Ok(Condition::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Condition, String> {
+        if self.binary_ahead() {
+            let (left, op, right) = (self.next(), self.next(), self.next());
+            return Ok(Condition::Binary(left.unwrap(), op.unwrap(), right.unwrap()));
+        }
+        match self.next() {
+            Some(word) if word == "(" => {
+                let condition = self.parse_or()?;
+                match self.next() {
+                    Some(word) if word == ")" => Ok(condition),
+                    Some(word) => Err(format!("Expected ) but found {}", word)),
+                    None => Err("Expected ) but found nothing".to_string()),
+                }
+            }
+            Some(op) if UNARY_OPERATORS.contains(&op.as_str()) && self.peek().is_some() => {
+                let operand = self.next().unwrap();
+                Ok(Condition::Unary(op, operand))
+            }
+            Some(word) => Ok(Condition::Unary("-n".to_string(), word)),
+            None => Err("Expected a conditional expression but found nothing".to_string()),
+        }
+    }
+}
@@ -1 +1,268 @@
-pub const FOLDER_NAME: &str = "/programs/";
\ No newline at end of file
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConfigError;
+
+pub const FOLDER_NAME: &str = "programs";
+pub const PROGRAMS_VARIABLE: &str = "SHELL_PROGRAMS";
+pub const SYSTEM_RC_FILE: &str = "/etc/shellrc";
+pub const RC_FILE_NAME: &str = ".shellrc";
+pub const CONFIG_FILE: &str = ".config/shell/config.toml";
+pub const HISTORY_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub programs: Vec<PathBuf>,
+    pub history_size: usize,
+    pub history_path: Option<PathBuf>,
+    pub prompt: Option<String>,
+    pub options: Vec<(String, bool)>,
+    pub aliases: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<String>),
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            programs: vec![],
+            history_size: HISTORY_SIZE,
+            history_path: None,
+            prompt: None,
+            options: vec![],
+            aliases: vec![],
+        }
+    }
+}
+
+impl Config {
+    pub fn load(args: &[String]) -> Result<Config, ConfigError> {
+        let config = match args.iter().position(|arg| arg == "--config") {
+            Some(position) => match args.get(position + 1) {
+                Some(path) => Config::read(Path::new(path))?,
+                None => return Err(config_error(None, 0, "Expected a file after --config")),
+            },
+            None => match env::var("SHELL_CONFIG") {
+                Ok(path) if !path.is_empty() => Config::read(Path::new(&path))?,
+                _ => match home().map(|home| home.join(CONFIG_FILE)) {
+                    Some(path) if path.is_file() => Config::read(&path)?,
+                    _ => Config::default(),
+                },
+            },
+        };
+        config.with_overrides(|name| env::var(name).ok())
+    }
+
+    pub fn read(path: &Path) -> Result<Config, ConfigError> {
+        let display = path.display().to_string();
+        let source = fs::read_to_string(path)
+            .map_err(|error| config_error(Some(&display), 0, &format!("Can't read config file: {}", error)))?;
+        Config::parse(&source).map_err(|error| ConfigError { path: Some(display), ..error })
+    }
+
+    pub fn parse(source: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut section = String::new();
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                match name.strip_suffix(']').map(str::trim) {
+                    Some(name @ ("history" | "options" | "aliases")) => section = name.to_string(),
+                    Some(name) => return Err(config_error(None, number, &format!("Unknown section [{}]", name))),
+                    None => return Err(config_error(None, number, "Expected ] at the end of the section name")),
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(config_error(None, number, &format!("Expected key = value but found {}", line)));
+            };
+            let key = unquote(key.trim());
+            let value = parse_value(value.trim()).map_err(|message| config_error(None, number, &message))?;
+            config.assign(&section, &key, value).map_err(|message| config_error(None, number, &message))?;
+        }
+        Ok(config)
+    }
+
+    pub fn with_overrides(mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        if let Some(programs) = lookup(PROGRAMS_VARIABLE) {
+            self.programs = env::split_paths(&programs).map(|path| expand_home(&path.display().to_string())).collect();
+        }
+        if let Some(size) = lookup("SHELL_HISTSIZE") {
+            self.history_size = size.parse().map_err(|_| {
+                config_error(Some("SHELL_HISTSIZE"), 0, &format!("Expected a history size but found {}", size))
+            })?;
+        }
+        if let Some(path) = lookup("SHELL_HISTFILE") {
+            self.history_path = (!path.is_empty()).then(|| expand_home(&path));
+        }
+        if let Some(prompt) = lookup("SHELL_PROMPT") {
+            self.prompt = Some(prompt);
+        }
+        if let Some(options) = lookup("SHELL_OPTIONS") {
+            for option in options.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+                match option.split_once('=').map(|(name, value)| (name, parse_value(value))) {
+                    None => self.set_option(option, true),
+                    Some((name, Ok(Value::Boolean(enable)))) => self.set_option(name, enable),
+                    Some(_) => {
+                        return Err(config_error(Some("SHELL_OPTIONS"), 0, &format!("Expected name=true or name=false but found {}", option)));
+                    }
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn assign(&mut self, section: &str, key: &str, value: Value) -> Result<(), String> {
+        match (section, key, value) {
+            ("", "programs", Value::String(path)) => self.programs = vec![expand_home(&path)],
+            ("", "programs", Value::Array(paths)) => self.programs = paths.iter().map(|path| expand_home(path)).collect(),
+            ("", "prompt", Value::String(prompt)) => self.prompt = Some(prompt),
+            ("history", "size", Value::Integer(size)) if size >= 0 => self.history_size = size as usize,
+            ("history", "path", Value::String(path)) => self.history_path = (!path.is_empty()).then(|| expand_home(&path)),
+            ("options", name, Value::Boolean(enable)) => self.set_option(name, enable),
+            ("aliases", name, Value::String(value)) => {
+                self.aliases.retain(|(alias, _)| alias != name);
+                self.aliases.push((name.to_string(), value));
+            }
+            ("", "programs" | "prompt", _) | ("history", "size" | "path", _) | ("options" | "aliases", _, _) => {
+                return Err(format!("Invalid value for {}", key));
+            }
+            _ => return Err(format!("Unknown key {}", key)),
+        }
+        Ok(())
+    }
+
+    fn set_option(&mut self, name: &str, enable: bool) {
+        self.options.retain(|(option, _)| option != name);
+        self.options.push((name.to_string(), enable));
+    }
+}
+
+fn config_error(path: Option<&str>, line: usize, message: &str) -> ConfigError {
+    ConfigError { path: path.map(str::to_string), line, message: message.to_string() }
+}
+
+fn home() -> Option<PathBuf> {
+    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok().filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~"), home()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, char) in line.char_indices() {
+        match (quote, char) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), char) if char == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(char),
+            (None, '#') => return &line[..index],
+            _ => (),
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn unquote(key: &str) -> String {
+    match parse_value(key) {
+        Ok(Value::String(key)) => key,
+        _ => key.to_string(),
+    }
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    let mut chars = value.chars().peekable();
+    let parsed = match chars.peek() {
+        Some('[') => {
+            chars.next();
+            let mut items = vec![];
+            loop {
+                skip_whitespace(&mut chars);
+                match chars.peek() {
+                    Some(']') => {
+                        chars.next();
+                        break;
+                    }
+                    Some(_) => items.push(parse_string(&mut chars)?),
+                    None => return Err("Expected ] at the end of the array".to_string()),
+                }
+                skip_whitespace(&mut chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => break,
+                    _ => return Err("Expected , or ] in the array".to_string()),
+                }
+            }
+            Value::Array(items)
+        }
+        Some('"' | '\'') => Value::String(parse_string(&mut chars)?),
+        _ => {
+            let word = chars.by_ref().collect::<String>();
+            return match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                word => match word.replace('_', "").parse::<i64>() {
+                    Ok(number) => Ok(Value::Integer(number)),
+                    Err(_) if word.chars().all(|c| c.is_alphanumeric() || "-_".contains(c)) => Ok(Value::String(word.to_string())),
+                    Err(_) => Err(format!("Expected a value but found {}", word)),
+                },
+            };
+        }
+    };
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(parsed),
+        Some(char) => Err(format!("Unexpected {} after the value", char)),
+    }
+}
+
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let quote = match chars.next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        _ => return Err("Expected a quoted string".to_string()),
+    };
+    let mut result = String::new();
+    while let Some(char) = chars.next() {
+        match char {
+            char if char == quote => return Ok(result),
+            '\\' if quote == '"' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('e') => result.push('\x1b'),
+                Some(char @ ('\\' | '"')) => result.push(char),
+                Some(char) => {
+                    result.push('\\');
+                    result.push(char);
+                }
+                None => break,
+            },
+            char => result.push(char),
+        }
+    }
+    Err(format!("Expected {} at the end of the string", quote))
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|char| char.is_whitespace()).is_some() {}
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Clone, Default)]
+pub enum Environment {
+    #[default]
+    Process,
+    Isolated {
+        cwd: PathBuf,
+        variables: HashMap<String, String>,
+    },
+}
+
+pub enum Snapshot {
+    Process(Option<PathBuf>, Vec<(OsString, OsString)>),
+    Isolated(PathBuf, HashMap<String, String>),
+}
+
+impl Environment {
+    pub fn isolated(cwd: PathBuf, variables: HashMap<String, String>) -> Environment {
+        Environment::Isolated { cwd, variables }
+    }
+
+    pub fn var(&self, name: &str) -> Option<String> {
+        match self {
+            Environment::Process => env::var(name).ok(),
+            Environment::Isolated { variables, .. } => variables.get(name).cloned(),
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        match self {
+            Environment::Process => env::set_var(name, value),
+            Environment::Isolated { variables, .. } => {
+                variables.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+
+    pub fn remove_var(&mut self, name: &str) {
+        match self {
+            Environment::Process => env::remove_var(name),
+            Environment::Isolated { variables, .. } => {
+                variables.remove(name);
+            }
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let mut vars = match self {
+            Environment::Process => env::vars().collect::<Vec<(String, String)>>(),
+            Environment::Isolated { variables, .. } => variables.clone().into_iter().collect(),
+        };
+        vars.sort();
+        vars
+    }
+
+    pub fn current_dir(&self) -> PathBuf {
+        match self {
+            Environment::Process => env::current_dir().unwrap_or_default(),
+            Environment::Isolated { cwd, .. } => cwd.clone(),
+        }
+    }
+
+    pub fn set_current_dir(&mut self, path: &Path) -> io::Result<()> {
+        match self {
+            Environment::Process => env::set_current_dir(path),
+            Environment::Isolated { cwd, .. } => match cwd.join(path) {
+                target if target.is_dir() => {
+                    *cwd = target;
+                    Ok(())
+                }
+                _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+            },
+        }
+    }
+
+    pub fn base(&self) -> &Path {
+        match self {
+            Environment::Process => Path::new(""),
+            Environment::Isolated { cwd, .. } => cwd,
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.base().join(path)
+    }
+
+    pub fn apply(&self, command: &mut Command) {
+        if let Environment::Isolated { cwd, variables } = self {
+            command.current_dir(cwd).env_clear().envs(variables);
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        match self {
+            Environment::Process => Snapshot::Process(env::current_dir().ok(), env::vars_os().collect()),
+            Environment::Isolated { cwd, variables } => Snapshot::Isolated(cwd.clone(), variables.clone()),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        match snapshot {
+            Snapshot::Process(cwd, vars) => {
+                if let Some(cwd) = cwd {
+                    let _ = env::set_current_dir(cwd);
+                }
+                for (key, _) in env::vars_os() {
+                    if !vars.iter().any(|(k, _)| *k == key) {
+                        env::remove_var(key);
+                    }
+                }
+                for (key, value) in vars {
+                    env::set_var(key, value);
+                }
+            }
+            Snapshot::Isolated(cwd, variables) => *self = Environment::Isolated { cwd, variables },
+        }
+    }
+}
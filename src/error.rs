@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::token::{Span, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    pub expected: Vec<Token>,
+    pub found: Option<Token>,
+}
+
+impl ParseError {
+    pub fn render(&self, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[line_start..].find('\n').map_or(source.len(), |index| line_start + index);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+
+        let indent = source[line_start..start].chars().count();
+        let width = source[start..self.span.end.clamp(start, line_end)].chars().count().max(1);
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!("{}\n{} |\n{} | {}\n{} | {}{}",
+                self.message, gutter, number, line, gutter, " ".repeat(indent), "^".repeat(width))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpandError {
+    UnboundVariable(String),
+    Arithmetic(String),
+    Conditional(String),
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpandError::UnboundVariable(name) => write!(f, "{}: unbound variable", name),
+            ExpandError::Arithmetic(message) | ExpandError::Conditional(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ExpandError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    CommandNotFound(String),
+    PermissionDenied(String),
+    Spawn { path: String, kind: io::ErrorKind },
+    RedirectInput { path: String, kind: io::ErrorKind },
+    RedirectOutput { path: String, kind: io::ErrorKind },
+    Builtin(String),
+}
+
+impl ExecError {
+    pub fn spawn(path: &str, error: &io::Error) -> ExecError {
+        match error.kind() {
+            io::ErrorKind::NotFound => ExecError::CommandNotFound(path.to_string()),
+            io::ErrorKind::PermissionDenied => ExecError::PermissionDenied(path.to_string()),
+            kind => ExecError::Spawn { path: path.to_string(), kind },
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::CommandNotFound(path) => write!(f, "Command not found: {}", path),
+            ExecError::PermissionDenied(path) => write!(f, "Permission denied: {}", path),
+            ExecError::Spawn { path, kind } => write!(f, "Could not run {}: {}", path, io::Error::from(*kind)),
+            ExecError::RedirectInput { path, kind: io::ErrorKind::NotFound } => write!(f, "File does not exist: {}", path),
+            ExecError::RedirectInput { path, .. } => write!(f, "Could not read file: {}", path),
+            ExecError::RedirectOutput { path, .. } => write!(f, "Could not create file: {}", path),
+            ExecError::Builtin(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ExecError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellError {
+    Parse(ParseError),
+    Expand(ExpandError),
+    Exec(ExecError),
+    Open { path: String, kind: io::ErrorKind },
+    Read { path: String, kind: io::ErrorKind },
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::Parse(error) => write!(f, "{}", error),
+            ShellError::Expand(error) => write!(f, "{}", error),
+            ShellError::Exec(error) => write!(f, "{}", error),
+            ShellError::Open { path, .. } => write!(f, "Can't open file: {}", path),
+            ShellError::Read { path, .. } => write!(f, "Can't read file: {}", path),
+        }
+    }
+}
+
+impl Error for ShellError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShellError::Parse(error) => Some(error),
+            ShellError::Expand(error) => Some(error),
+            ShellError::Exec(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for ShellError {
+    fn from(error: ParseError) -> ShellError {
+        ShellError::Parse(error)
+    }
+}
+
+impl From<ExpandError> for ShellError {
+    fn from(error: ExpandError) -> ShellError {
+        ShellError::Expand(error)
+    }
+}
+
+impl From<ExecError> for ShellError {
+    fn from(error: ExecError) -> ShellError {
+        ShellError::Exec(error)
+    }
+}
+
+impl From<String> for ShellError {
+    fn from(message: String) -> ShellError {
+        ShellError::Exec(ExecError::Builtin(message))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub path: Option<String>,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), 0) => write!(f, "{}: {}", path, self.message),
+            (Some(path), line) => write!(f, "{}:{}: {}", path, line, self.message),
+            (None, 0) => write!(f, "{}", self.message),
+            (None, line) => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
@@ -0,0 +1,190 @@
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::glob;
+
+enum Piece {
+    Quoted(String),
+    Literal(String),
+    Expanded(String),
+}
+
+struct Field {
+    value: String,
+    pattern: String,
+    globbing: bool,
+}
+
+struct Fields {
+    fields: Vec<Field>,
+    current: Option<Field>,
+}
+
+impl Fields {
+    fn new() -> Fields {
+        Fields { fields: vec![], current: None }
+    }
+
+    fn current(&mut self) -> &mut Field {
+        self.current.get_or_insert_with(|| Field { value: String::new(), pattern: String::new(), globbing: false })
+    }
+
+    fn push(&mut self, piece: Piece) {
+        match piece {
+            Piece::Quoted(quoted) => {
+                let field = self.current();
+                field.value.push_str(&quoted);
+                field.pattern.push_str(&glob::escape(&quoted));
+            }
+            Piece::Literal(literal) => self.push_unquoted(&literal),
+            Piece::Expanded(value) => {
+                for (i, part) in value.split(char::is_whitespace).enumerate() {
+                    if i > 0 {
+                        self.finish_field();
+                    }
+                    if !part.is_empty() {
+                        self.push_unquoted(part);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push_unquoted(&mut self, text: &str) {
+        let field = self.current();
+        field.value.push_str(text);
+        field.pattern.push_str(&text.replace('\\', "\\\\"));
+        field.globbing |= glob::is_pattern(text);
+    }
+
+    fn finish_field(&mut self) {
+        if let Some(field) = self.current.take() {
+            self.fields.push(field);
+        }
+    }
+
+    fn into_fields(mut self, base: &Path) -> Vec<String> {
+        self.finish_field();
+        self.fields
+            .into_iter()
+            .flat_map(|field| {
+                let paths = if field.globbing { glob::expand_path(&field.pattern, base) } else { vec![] };
+                if paths.is_empty() { vec![field.value] } else { paths }
+            })
+            .collect()
+    }
+}
+
+pub fn expand_word(word: &str, lookup: &dyn Fn(&str) -> Option<String>, base: &Path) -> Vec<String> {
+    let mut fields = Fields::new();
+    split_pieces(word, lookup, &mut |piece| fields.push(piece));
+    fields.into_fields(base)
+}
+
+pub fn expand_string(word: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    split_pieces(word, lookup, &mut |piece| match piece {
+        Piece::Quoted(text) | Piece::Literal(text) | Piece::Expanded(text) => result.push_str(&text),
+    });
+    result
+}
+
+pub fn expand_pattern(word: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut pattern = String::new();
+    split_pieces(word, lookup, &mut |piece| match piece {
+        Piece::Quoted(text) => pattern.push_str(&glob::escape(&text)),
+        Piece::Literal(text) | Piece::Expanded(text) => pattern.push_str(&text.replace('\\', "\\\\")),
+    });
+    pattern
+}
+
+pub fn expand_regex(word: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut regex = String::new();
+    split_pieces(word, lookup, &mut |piece| match piece {
+        Piece::Quoted(text) => regex.push_str(&regex::escape(&text)),
+        Piece::Literal(text) | Piece::Expanded(text) => regex.push_str(&text),
+    });
+    regex
+}
+
+fn split_pieces(word: &str, lookup: &dyn Fn(&str) -> Option<String>, sink: &mut dyn FnMut(Piece)) {
+    let mut chars = word.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        let piece = match c {
+            '\'' => Piece::Quoted(read_until(&mut chars, '\'')),
+            '"' => Piece::Quoted(expand_quoted(&read_until(&mut chars, '"'), lookup)),
+            '$' => match read_parameter(&mut chars) {
+                Some(name) => Piece::Expanded(lookup(&name).unwrap_or_default()),
+                None => {
+                    literal.push('$');
+                    continue;
+                }
+            },
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+        if !literal.is_empty() {
+            sink(Piece::Literal(std::mem::take(&mut literal)));
+        }
+        sink(piece);
+    }
+    if !literal.is_empty() {
+        sink(Piece::Literal(literal));
+    }
+}
+
+fn expand_quoted(quoted: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match read_parameter(&mut chars) {
+            Some(name) => result.push_str(&lookup(&name).unwrap_or_default()),
+            None => result.push('$'),
+        }
+    }
+    result
+}
+
+fn read_until(chars: &mut Peekable<Chars>, end: char) -> String {
+    let mut result = String::new();
+    for c in chars.by_ref() {
+        if c == end {
+            break;
+        }
+        result.push(c);
+    }
+    result
+}
+
+pub fn read_parameter(chars: &mut Peekable<Chars>) -> Option<String> {
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            Some(read_until(chars, '}'))
+        }
+        Some(&c) if matches!(c, '?' | '$' | '#' | '@' | '*' | '!') || c.is_ascii_digit() => {
+            chars.next();
+            Some(c.to_string())
+        }
+        Some(&c) if c.is_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            Some(name)
+        }
+        _ => None,
+    }
+}
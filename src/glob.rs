@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+
+pub fn is_pattern(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => { chars.next(); }
+            '*' | '?' | '[' => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+pub fn escape(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+pub fn unescape(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+            continue;
+        }
+        if let Some(next) = match_char(&pattern, p, text[t]) {
+            p = next;
+            t += 1;
+            continue;
+        }
+        match star {
+            Some((star_p, star_t)) => {
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn match_char(pattern: &[char], p: usize, c: char) -> Option<usize> {
+    match pattern.get(p)? {
+        '?' => Some(p + 1),
+        '[' => match match_class(pattern, p, c) {
+            Some((true, next)) => Some(next),
+            Some((false, _)) => None,
+            None if c == '[' => Some(p + 1),
+            None => None,
+        },
+        '\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        &literal => (literal == c).then_some(p + 1),
+    }
+}
+
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut p = start + 1;
+    let negated = matches!(pattern.get(p), Some('!' | '^'));
+    if negated {
+        p += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut low = *pattern.get(p)?;
+        if low == ']' && !first {
+            return Some((matched != negated, p + 1));
+        }
+        first = false;
+        if low == '\\' {
+            p += 1;
+            low = *pattern.get(p)?;
+        }
+        p += 1;
+
+        let mut high = low;
+        if pattern.get(p) == Some(&'-') && pattern.get(p + 1).is_some_and(|&next| next != ']') {
+            high = pattern[p + 1];
+            if high == '\\' {
+                p += 1;
+                high = *pattern.get(p + 1)?;
+            }
+            p += 2;
+        }
+        if low <= c && c <= high {
+            matched = true;
+        }
+    }
+}
+
+pub fn expand_path(pattern: &str, base: &Path) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    for component in rest.split('/').filter(|component| !component.is_empty()) {
+        let mut next = Vec::new();
+        for path in &paths {
+            if !is_pattern(component) {
+                next.push(join(path, &unescape(component)));
+                continue;
+            }
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let entries = match fs::read_dir(base.join(dir)) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names = entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                .filter(|name| matches(component, name))
+                .map(|name| join(path, &name))
+                .collect::<Vec<String>>();
+            names.sort();
+            next.extend(names);
+        }
+        paths = next;
+    }
+
+    if pattern.ends_with('/') {
+        paths = paths.into_iter().filter(|path| base.join(path).is_dir()).map(|path| path + "/").collect();
+    }
+    paths.into_iter().filter(|path| base.join(path).symlink_metadata().is_ok()).collect()
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() || path.ends_with('/') {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::builtins::Streams;
+use crate::interpreter::Interpreter;
+
+pub const TOPICS: &[(&str, &str, &str)] = &[
+    ("pipelines", "Connect commands with |, &&, ||, ; and !",
+     "cmd1 | cmd2\n    Feed the standard output of cmd1 to the standard input of cmd2.\n\
+      cmd1 && cmd2\n    Run cmd2 only if cmd1 succeeds.\n\
+      cmd1 || cmd2\n    Run cmd2 only if cmd1 fails.\n\
+      cmd1 ; cmd2\n    Run cmd1 and then cmd2.\n\
+      ! pipeline\n    Negate the status of the pipeline.\n\
+      time [-p] pipeline\n    Report the time taken by the pipeline."),
+    ("redirects", "Read from and write to files with <, > and 2>",
+     "cmd < file\n    Read the standard input from file.\n\
+      cmd > file\n    Write the standard output to file, replacing its contents.\n\
+      cmd 2> file\n    Write the standard error to file, replacing its contents."),
+    ("branching", "Choose commands with if and case",
+     "if list; then list; [elif list; then list;] ... [else list;] fi\n    \
+      Run the then list of the first condition that succeeds, or the else list if none does.\n\
+      case word in [pattern [| pattern] ...) list ;;] ... esac\n    \
+      Run the list of the first pattern matching word. End an arm with ;& to fall through\n    \
+      or ;;& to keep testing the following patterns."),
+    ("loops", "Repeat commands with while, until and for",
+     "while list; do list; done\n    Run the body as long as the condition succeeds.\n\
+      until list; do list; done\n    Run the body as long as the condition fails.\n\
+      for name [in word ...]; do list; done\n    Run the body with name set to each word, or each positional parameter.\n\
+      for ((init; condition; step)); do list; done\n    Run the body while the arithmetic condition is non-zero."),
+    ("functions", "Define commands from a list of commands",
+     "name () { list; }\nfunction name { list; }\n    \
+      Define a function that runs list with its arguments as positional parameters."),
+    ("groups", "Group commands in the current shell or in a subshell",
+     "{ list; }\n    Run list in the current shell.\n\
+      ( list )\n    Run list in a subshell, so changes to variables and the directory are discarded."),
+    ("tests", "Evaluate arithmetic and conditional expressions",
+     "(( expression ))\n    Succeed if the arithmetic expression is non-zero.\n\
+      [[ expression ]]\n    Evaluate a conditional expression with pattern and regex matching."),
+    ("expansion", "Expand variables and file name patterns",
+     "$name ${name} ${#name}\n    Expand a variable, or the length of its value.\n\
+      $? $# $@ $* $0 $1 ...\n    Expand the last status, the number of arguments and the positional parameters.\n\
+      * ? [abc]\n    Expand to the file names matching the pattern."),
+];
+
+const KEYWORDS: &[(&str, &str)] = &[
+    ("!", "pipelines"), ("time", "pipelines"),
+    ("if", "branching"), ("then", "branching"), ("elif", "branching"), ("else", "branching"), ("fi", "branching"),
+    ("case", "branching"), ("esac", "branching"),
+    ("while", "loops"), ("until", "loops"), ("for", "loops"), ("do", "loops"), ("done", "loops"),
+    ("function", "functions"),
+    ("{", "groups"), ("}", "groups"),
+    ("((", "tests"), ("[[", "tests"), ("]]", "tests"),
+];
+
+fn find_topic(topic: &str) -> Option<&'static str> {
+    let topic = KEYWORDS.iter().find(|(keyword, _)| *keyword == topic).map_or(topic, |(_, name)| name);
+    TOPICS.iter().find(|(name, _, _)| *name == topic).map(|(_, _, help)| *help)
+}
+
+pub fn help(interpreter: &mut Interpreter, arguments: &[String], streams: &mut Streams) -> bool {
+    if arguments.is_empty() {
+        let mut output = String::from("Grammar topics, see help TOPIC for details:\n");
+        for (name, summary, _) in TOPICS {
+            output.push_str(&format!("    {:<15}{}\n", name, summary));
+        }
+        output.push_str("\nBuiltins, see help NAME for details:\n");
+        for builtin in interpreter.builtins() {
+            output.push_str(&format!("    {}\n", builtin.help().lines().next().unwrap_or(builtin.name())));
+        }
+        streams.print(&output);
+        return true;
+    }
+
+    let mut success = true;
+    for topic in arguments {
+        match (find_topic(topic), interpreter.builtin_help(topic)) {
+            (_, Some(help)) => streams.print(&(help + "\n")),
+            (Some(help), None) => streams.print(&format!("{}\n", help)),
+            (None, None) => {
+                interpreter.report_to(streams.stderr, format!("No help topics match {}", topic));
+                success = false;
+            }
+        }
+    }
+    success
+}
@@ -1,161 +1,1949 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use std::process::{ChildStderr, ChildStdout, Command, exit, Stdio};
-use crate::ast::{Expr, Operator, Redirect};
-use crate::utils::is_dir;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path};
+use std::process::{Child, Command, exit, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use regex::Regex;
+
+use crate::arithmetic;
+use crate::arithmetic::Variables;
+use crate::ast::{CaseArm, CaseTerminator, Condition, Expr, Operator, Redirect};
+use crate::builtins::{Builtin, Streams, INTERNAL};
+use crate::conditional;
+use crate::config::Config;
+use crate::error::{ExecError, ExpandError, ParseError, ShellError};
+use crate::environment::{Environment, Snapshot};
+use crate::expand::{expand_pattern, expand_regex, expand_string, expand_word};
+use crate::glob;
+use crate::lexer::Lexer;
+use crate::printf;
+use crate::printf::quote;
+use crate::prompt;
+use crate::shell::ExecResult;
+use crate::signals;
+use crate::terminal;
+use crate::timing;
+use crate::timing::Times;
+use crate::utils;
+use crate::utils::{is_incomplete, normalize_path, parse_with_aliases};
+
+const MAX_FUNCTION_DEPTH: usize = 256;
+const OPTIONS: [(&str, Option<char>); 7] = [
+    ("errexit", Some('e')),
+    ("ignoreeof", None),
+    ("noexec", Some('n')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("verbose", Some('v')),
+    ("xtrace", Some('x')),
+];
+
+struct Trap {
+    command: String,
+    handler: Option<Expr>,
+}
+
+enum Sink {
+    File(File),
+    Capture(Vec<u8>),
+    Stream(Vec<u8>, Box<dyn Write>),
+}
+
+impl Sink {
+    fn write(&mut self, buffer: &[u8]) {
+        match self {
+            Sink::File(file) => { let _ = file.write_all(buffer); }
+            Sink::Capture(output) => output.extend(buffer),
+            Sink::Stream(output, writer) => {
+                output.extend(buffer);
+                let _ = writer.write_all(buffer).and_then(|_| writer.flush());
+            }
+        }
+    }
+
+    fn is_captured(&self) -> bool {
+        matches!(self, Sink::Capture(_) | Sink::Stream(..))
+    }
+}
+
+enum Output {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+#[derive(PartialEq)]
+enum Flow {
+    Normal,
+    Break(usize),
+    Continue(usize),
+    Return,
+    Exit,
+}
+
+struct Frame {
+    arguments: Vec<String>,
+    locals: Vec<(String, Option<String>, Option<String>)>,
+    function: bool,
+}
+
+struct SubshellState {
+    environment: Snapshot,
+    variables: HashMap<String, String>,
+    arrays: HashMap<String, Vec<String>>,
+    traps: HashMap<&'static str, Trap>,
+    functions: HashMap<String, Expr>,
+    aliases: HashMap<String, String>,
+    directories: Vec<String>,
+    arguments: Vec<String>,
+    options: HashSet<&'static str>,
+    locals: Vec<usize>,
+}
 
 pub struct Interpreter {
-    stderr: Option<ChildStderr>,
-    stdout: Option<ChildStdout>,
-    exit_success: Vec<bool>,
+    exit_status: Vec<i32>,
+
+    is_piped: bool,
+    pipeline_depth: usize,
+    condition_depth: usize,
+    subshell_depth: usize,
+    loop_depth: usize,
+    flow: Flow,
+
+    variables: HashMap<String, String>,
+    arrays: HashMap<String, Vec<String>>,
+    functions: HashMap<String, Expr>,
+    frames: Vec<Frame>,
+    aliases: HashMap<String, String>,
+    directories: Vec<String>,
+    arguments: Vec<String>,
+    options: HashSet<&'static str>,
+    unbound: RefCell<Option<String>>,
+    expanding_prompt: bool,
+
+    piped_input: Option<Vec<u8>>,
+    stdin_files: Vec<File>,
+    stdout_sinks: Vec<Sink>,
+    stderr_sinks: Vec<Sink>,
+    stdout_writer: Option<Box<dyn Write>>,
+    stderr_writer: Option<Box<dyn Write>>,
+
+    traps: HashMap<&'static str, Trap>,
+    running_trap: bool,
+
+    capture: bool,
+    embedded: bool,
+    exit_code: Option<i32>,
+    output_result: Vec<String>,
+    error_result: Vec<String>,
+    errors: Vec<ShellError>,
+
+    environment: Environment,
+    builtins: HashMap<String, Rc<dyn Builtin>>,
+    program_dir: String,
+}
+
+impl Interpreter {
+    pub fn new(program_path: &str) -> Interpreter {
+        Interpreter {
+            exit_status: vec![],
+
+            is_piped: false,
+            pipeline_depth: 0,
+            condition_depth: 0,
+            subshell_depth: 0,
+            loop_depth: 0,
+            flow: Flow::Normal,
+
+            variables: HashMap::new(),
+            arrays: HashMap::new(),
+            functions: HashMap::new(),
+            frames: vec![],
+            aliases: HashMap::new(),
+            directories: vec![],
+            arguments: vec![],
+            options: HashSet::new(),
+            unbound: RefCell::new(None),
+            expanding_prompt: false,
+
+            piped_input: None,
+            stdin_files: vec![],
+            stdout_sinks: vec![],
+            stderr_sinks: vec![],
+            stdout_writer: None,
+            stderr_writer: None,
+
+            traps: HashMap::new(),
+            running_trap: false,
+
+            capture: false,
+            embedded: false,
+            exit_code: None,
+            output_result: vec![],
+            error_result: vec![],
+            errors: vec![],
+
+            environment: Environment::Process,
+            builtins: INTERNAL.iter().map(|builtin| (builtin.name().to_string(), Rc::new(*builtin) as Rc<dyn Builtin>)).collect(),
+            program_dir: program_path.to_string(),
+        }
+    }
+
+    pub fn with_capture(mut self) -> Interpreter {
+        self.capture = true;
+        self
+    }
+
+    pub fn with_writers(mut self, stdout: Option<Box<dyn Write>>, stderr: Option<Box<dyn Write>>) -> Interpreter {
+        self.stdout_writer = stdout;
+        self.stderr_writer = stderr;
+        self
+    }
+
+    pub fn with_environment(mut self, environment: Environment) -> Interpreter {
+        self.environment = environment;
+        self
+    }
+
+    pub fn with_config(mut self, config: &Config) -> Interpreter {
+        for (name, enable) in &config.options {
+            match OPTIONS.iter().find(|(option, _)| option == name) {
+                Some((option, _)) => self.set_option(option, *enable),
+                None => self.report(format!("{} is not a valid option", name)),
+            }
+        }
+        for (name, value) in &config.aliases {
+            self.aliases.insert(name.to_string(), value.to_string());
+        }
+        if let Some(prompt) = &config.prompt {
+            Variables::set(&mut self, "PS1", prompt.to_string());
+        }
+        self
+    }
+
+    pub fn with_builtin(mut self, builtin: impl Builtin + 'static) -> Interpreter {
+        self.register(builtin);
+        self
+    }
+
+    pub fn register(&mut self, builtin: impl Builtin + 'static) {
+        self.builtins.insert(builtin.name().to_string(), Rc::new(builtin));
+    }
+
+    pub fn builtin_help(&self, name: &str) -> Option<String> {
+        self.builtins.get(name).map(|builtin| builtin.help().to_string())
+    }
+
+    pub fn builtins(&self) -> Vec<Rc<dyn Builtin>> {
+        let mut builtins = self.builtins.values().cloned().collect::<Vec<Rc<dyn Builtin>>>();
+        builtins.sort_by(|a, b| a.name().cmp(b.name()));
+        builtins
+    }
+
+    pub fn eval(&mut self, ast: &Expr) -> (Vec<String>, Vec<String>) {
+        self.exit_status = vec![];
+        self.output_result = vec![];
+        self.error_result = vec![];
+        self.errors = vec![];
+
+        self.eval_expr(ast);
+        self.run_signal_traps();
+        (std::mem::take(&mut self.error_result), std::mem::take(&mut self.output_result))
+    }
+
+    pub fn run(&mut self, input: &str) -> ExecResult {
+        self.embedded = true;
+        self.exit_code = None;
+        self.exit_status = vec![];
+        self.errors = vec![];
+        let sink = |writer: Option<Box<dyn Write>>| match writer {
+            Some(writer) => Sink::Stream(vec![], writer),
+            None => Sink::Capture(vec![]),
+        };
+        self.stdout_sinks.push(sink(self.stdout_writer.take()));
+        self.stderr_sinks.push(sink(self.stderr_writer.take()));
+
+        let status = self.eval_script(input);
+        let parsed = status == 0 || !self.errors.iter().any(|error| matches!(error, ShellError::Parse(_)));
+        if self.exit_code.is_some() {
+            let flow = std::mem::replace(&mut self.flow, Flow::Normal);
+            self.run_trap(signals::EXIT);
+            self.flow = flow;
+        }
+        self.run_signal_traps();
+        self.flow = Flow::Normal;
+
+        let status = match self.exit_code.take() {
+            Some(code) => code,
+            None if !parsed => 2,
+            None => status,
+        };
+        let take = |sinks: &mut Vec<Sink>, writer: &mut Option<Box<dyn Write>>| match sinks.pop() {
+            Some(Sink::Capture(output)) => output,
+            Some(Sink::Stream(output, stream)) => {
+                *writer = Some(stream);
+                output
+            }
+            _ => vec![],
+        };
+        let stderr = take(&mut self.stderr_sinks, &mut self.stderr_writer);
+        let stdout = take(&mut self.stdout_sinks, &mut self.stdout_writer);
+        ExecResult { status, stdout, stderr }
+    }
+
+    pub fn eval_file(&mut self, path: &str) -> (Vec<String>, Vec<String>) {
+        self.exit_status = vec![];
+        self.output_result = vec![];
+        self.error_result = vec![];
+        self.errors = vec![];
+
+        let mut stderr = vec![];
+        let status = self.source_file(&mut stderr, &[path.to_string()]);
+        self.push_error_output(stderr);
+        self.exit_status.push(status);
+        self.run_signal_traps();
+        (std::mem::take(&mut self.error_result), std::mem::take(&mut self.output_result))
+    }
+
+    fn eval_script(&mut self, script: &str) -> i32 {
+        self.exit_status.push(0);
+        let mut input = String::new();
+        let mut start = 1;
+        for (index, line) in script.lines().enumerate() {
+            if input.is_empty() {
+                start = index + 1;
+            }
+            input.push_str(line);
+            input.push('\n');
+            if self.option("verbose") {
+                self.push_error_result(line.to_string());
+            }
+            if input.trim().is_empty() || is_incomplete(&input, &self.program_dir, &self.aliases) {
+                continue;
+            }
+            match self.parse_at(&input, start) {
+                Some(_) if self.option("noexec") => (),
+                Some(ast) => {
+                    self.eval_expr(&ast);
+                }
+                None => return 1,
+            }
+            input.clear();
+            if self.flow != Flow::Normal {
+                break;
+            }
+        }
+        if !input.trim().is_empty() && self.parse_at(&input, start).is_none() {
+            return 1;
+        }
+        self.last_status()
+    }
+
+    fn last_status(&self) -> i32 {
+        *self.exit_status.last().unwrap_or(&0)
+    }
+
+    fn eval_expr(&mut self, node: &Expr) {
+        if self.flow != Flow::Normal {
+            return;
+        }
+        match node {
+            Expr::Binary(lhs, Operator::Pipe, rhs) => {
+                let statuses = self.exit_status.len();
+                self.pipeline_depth += 1;
+                self.piped_input = Some(self.capture_stage(lhs));
+                self.is_piped = true;
+                self.eval_expr(rhs);
+                self.is_piped = false;
+                self.piped_input = None;
+                self.pipeline_depth -= 1;
+                let failed = self.exit_status.get(statuses..).and_then(|statuses| statuses.iter().rev().find(|&&status| status != 0));
+                if let Some(&status) = failed.filter(|_| self.option("pipefail")) {
+                    self.exit_status.push(status);
+                }
+                self.run_traps();
+                if self.pipeline_depth == 0 {
+                    self.check_errexit();
+                }
+            }
+            Expr::Binary(lhs, Operator::Next, rhs) => {
+                self.eval_expr(lhs);
+                self.eval_expr(rhs);
+            }
+            Expr::Binary(lhs, Operator::NextIfSuccess, rhs) => {
+                self.eval_expr(lhs);
+                if self.exit_status.last() == Some(&0) {
+                    self.eval_expr(rhs);
+                }
+            }
+            Expr::Binary(lhs, Operator::LogicOr, rhs) => {
+                self.process_logic(lhs, rhs, false)
+            }
+            Expr::Binary(lhs, Operator::LogicAnd, rhs) => {
+                self.process_logic(lhs, rhs, true)
+            }
+            Expr::If(cond, then_expr) => {
+                self.eval_condition(cond);
+                let condition = self.exit_status.pop() == Some(0);
+                if condition { self.eval_expr(then_expr) }
+            }
+            Expr::IfElse(cond, then_expr, else_expr) => {
+                self.eval_condition(cond);
+                let condition = self.exit_status.pop() == Some(0);
+                self.eval_expr(if condition { then_expr } else { else_expr });
+            }
+            Expr::While(cond, body) => {
+                self.eval_loop(cond, body, true);
+            }
+            Expr::Until(cond, body) => {
+                self.eval_loop(cond, body, false);
+            }
+            Expr::For(variable, words, body) => {
+                self.eval_for(variable, words, body);
+            }
+            Expr::ArithmeticFor(init, cond, step, body) => {
+                self.eval_arithmetic_for(init, cond, step, body);
+            }
+            Expr::Arithmetic(expression) => {
+                self.eval_arithmetic(expression);
+            }
+            Expr::Case(word, arms) => {
+                self.eval_case(word, arms);
+            }
+            Expr::Group(body, redirect) => {
+                self.eval_compound(body, redirect);
+            }
+            Expr::Subshell(body, redirect) => {
+                let state = self.enter_subshell();
+                self.eval_compound(body, redirect);
+                self.leave_subshell(state);
+                self.check_errexit();
+            }
+            Expr::Function(name, body) => {
+                self.functions.insert(name.to_string(), *body.clone());
+                self.exit_status.push(0);
+            }
+            Expr::Not(pipeline) => {
+                self.eval_condition(pipeline);
+                let success = self.exit_status.pop() == Some(0);
+                self.exit_status.push(i32::from(success));
+            }
+            Expr::Time(pipeline, posix) => {
+                self.eval_time(pipeline, *posix);
+            }
+            Expr::Conditional(condition) => {
+                let success = match self.eval_conditional(condition) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        self.report(ExpandError::Conditional(e));
+                        false
+                    }
+                };
+                self.exit_status.push(i32::from(!success));
+                self.check_errexit();
+            }
+            Expr::Cmd { name: cmd_type, arguments, redirect } => {
+                self.execute(cmd_type, arguments, redirect);
+                if self.pipeline_depth == 0 {
+                    self.run_traps();
+                    self.check_errexit();
+                }
+            }
+        }
+    }
+
+    fn capture_stage(&mut self, stage: &Expr) -> Vec<u8> {
+        self.stdout_sinks.push(Sink::Capture(vec![]));
+        let state = self.enter_subshell();
+        self.eval_expr(stage);
+        self.leave_subshell(state);
+        match self.stdout_sinks.pop() {
+            Some(Sink::Capture(output)) => output,
+            _ => vec![],
+        }
+    }
+
+    fn eval_loop(&mut self, cond: &Expr, body: &Expr, expected: bool) {
+        let mut ran = false;
+        self.loop_depth += 1;
+        loop {
+            self.eval_condition(cond);
+            let condition = self.exit_status.pop() == Some(0);
+            if self.flow != Flow::Normal || condition != expected {
+                break;
+            }
+            ran = true;
+            if !self.eval_loop_body(body) {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+        if !ran {
+            self.exit_status.push(0);
+        }
+    }
+
+    fn eval_for(&mut self, variable: &str, words: &Option<Vec<String>>, body: &Expr) {
+        let values = match words {
+            Some(words) => self.expand_arguments(words),
+            None => self.positional().to_vec(),
+        };
+        if self.unbound_variable() {
+            self.exit_status.push(1);
+            return;
+        }
+        if values.is_empty() {
+            self.exit_status.push(0);
+            return;
+        }
+        self.loop_depth += 1;
+        for value in values {
+            self.set_variable(variable, value);
+            if !self.eval_loop_body(body) {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+    }
+
+    fn eval_arithmetic_for(&mut self, init: &str, cond: &str, step: &str, body: &Expr) {
+        let mut ran = false;
+        self.loop_depth += 1;
+        let mut expression = init;
+        loop {
+            if let Err(e) = arithmetic::evaluate(expression, self) {
+                self.report(ExpandError::Arithmetic(e));
+                self.exit_status.push(1);
+                break;
+            }
+            let condition = match cond.is_empty() {
+                true => Ok(1),
+                false => arithmetic::evaluate(cond, self),
+            };
+            match condition {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(e) => {
+                    self.report(ExpandError::Arithmetic(e));
+                    self.exit_status.push(1);
+                    break;
+                }
+            }
+            ran = true;
+            if !self.eval_loop_body(body) {
+                break;
+            }
+            expression = step;
+        }
+        self.loop_depth -= 1;
+        if !ran {
+            self.exit_status.push(0);
+        }
+    }
+
+    fn eval_arithmetic(&mut self, expression: &str) {
+        let status = match arithmetic::evaluate(expression, self) {
+            Ok(value) => i32::from(value == 0),
+            Err(e) => {
+                self.report(ExpandError::Arithmetic(e));
+                1
+            }
+        };
+        self.exit_status.push(status);
+        self.check_errexit();
+    }
+
+    fn eval_case(&mut self, word: &str, arms: &[CaseArm]) {
+        let word = expand_string(word, &|name| self.lookup(name));
+        if self.unbound_variable() {
+            self.exit_status.push(1);
+            return;
+        }
+        let mut ran = false;
+        let mut falling_through = false;
+        for arm in arms {
+            let matched = falling_through || arm.patterns
+                .iter()
+                .any(|pattern| glob::matches(&expand_pattern(pattern, &|name| self.lookup(name)), &word));
+            if !matched {
+                continue;
+            }
+
+            ran = true;
+            match &arm.body {
+                Some(body) => {
+                    self.eval_expr(body);
+                }
+                None => self.exit_status.push(0),
+            }
+            match arm.terminator {
+                CaseTerminator::Break => break,
+                CaseTerminator::FallThrough => falling_through = true,
+                CaseTerminator::Continue => falling_through = false,
+            }
+        }
+        if !ran {
+            self.exit_status.push(0);
+        }
+    }
+
+    fn eval_time(&mut self, pipeline: &Expr, posix: bool) {
+        let start = Instant::now();
+        let (user, sys) = timing::cpu_times();
+        self.eval_expr(pipeline);
+        let (end_user, end_sys) = timing::cpu_times();
+        let times = Times {
+            real: start.elapsed(),
+            user: end_user.saturating_sub(user),
+            sys: end_sys.saturating_sub(sys),
+        };
+
+        let format = match posix {
+            true => timing::POSIX_FORMAT.to_string(),
+            false => self.get("TIMEFORMAT").unwrap_or_else(|| timing::DEFAULT_FORMAT.to_string()),
+        };
+        if !format.is_empty() {
+            self.push_error_result(timing::format(&format, &times));
+        }
+    }
+
+    fn eval_conditional(&mut self, condition: &Condition) -> Result<bool, String> {
+        match condition {
+            Condition::Not(condition) => Ok(!self.eval_conditional(condition)?),
+            Condition::And(lhs, rhs) => Ok(self.eval_conditional(lhs)? && self.eval_conditional(rhs)?),
+            Condition::Or(lhs, rhs) => Ok(self.eval_conditional(lhs)? || self.eval_conditional(rhs)?),
+            Condition::Unary(op, operand) => {
+                conditional::unary(op, &expand_string(operand, &|name| self.lookup(name)), self.environment.base())
+            }
+            Condition::Binary(left, op, right) => {
+                let left = expand_string(left, &|name| self.lookup(name));
+                match op.as_str() {
+                    "=" | "==" => Ok(glob::matches(&expand_pattern(right, &|name| self.lookup(name)), &left)),
+                    "!=" => Ok(!glob::matches(&expand_pattern(right, &|name| self.lookup(name)), &left)),
+                    "=~" => {
+                        let pattern = expand_regex(right, &|name| self.lookup(name));
+                        self.match_regex(&left, &pattern)
+                    }
+                    _ => conditional::binary(op, &left, &expand_string(right, &|name| self.lookup(name))),
+                }
+            }
+        }
+    }
+
+    fn match_regex(&mut self, text: &str, pattern: &str) -> Result<bool, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid regular expression {}: {}", pattern, e))?;
+        let groups = match regex.captures(text) {
+            Some(captures) => captures
+                .iter()
+                .map(|group| group.map(|group| group.as_str().to_string()).unwrap_or_default())
+                .collect(),
+            None => vec![],
+        };
+        let matched = !groups.is_empty();
+        self.arrays.insert("BASH_REMATCH".to_string(), groups);
+        Ok(matched)
+    }
+
+    fn eval_loop_body(&mut self, body: &Expr) -> bool {
+        self.eval_expr(body);
+        match self.flow {
+            Flow::Break(n) => {
+                self.flow = if n > 1 { Flow::Break(n - 1) } else { Flow::Normal };
+                false
+            }
+            Flow::Continue(n) if n > 1 => {
+                self.flow = Flow::Continue(n - 1);
+                false
+            }
+            Flow::Continue(_) => {
+                self.flow = Flow::Normal;
+                true
+            }
+            Flow::Normal => true,
+            Flow::Return | Flow::Exit => false,
+        }
+    }
+
+    fn eval_compound(&mut self, body: &Expr, redirect: &Redirect) {
+        let redirect = &self.expand_redirect(redirect);
+        self.eval_redirected(body, redirect);
+    }
+
+    fn eval_redirected(&mut self, body: &Expr, redirect: &Redirect) {
+        self.with_redirects(redirect, |interpreter| interpreter.eval_expr(body));
+    }
+
+    fn with_redirects(&mut self, redirect: &Redirect, run: impl FnOnce(&mut Self)) {
+        if !self.push_redirects(redirect) {
+            self.exit_status.push(1);
+            return;
+        }
+        run(self);
+        self.pop_redirects(redirect);
+    }
+
+    fn push_redirects(&mut self, redirect: &Redirect) -> bool {
+        let stdin = match &redirect.stdin {
+            Some(filename) => match File::open(self.environment.resolve(filename)) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    self.report(ExecError::RedirectInput { path: filename.to_string(), kind: e.kind() });
+                    return false;
+                }
+            },
+            None => None,
+        };
+        let stdout = match &redirect.stdout {
+            Some(filename) => match File::create(self.environment.resolve(filename)) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    self.report(ExecError::RedirectOutput { path: filename.to_string(), kind: e.kind() });
+                    return false;
+                }
+            },
+            None => None,
+        };
+        let stderr = match &redirect.stderr {
+            Some(filename) => match File::create(self.environment.resolve(filename)) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    self.report(ExecError::RedirectOutput { path: filename.to_string(), kind: e.kind() });
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        self.stdin_files.extend(stdin);
+        self.stdout_sinks.extend(stdout.map(Sink::File));
+        self.stderr_sinks.extend(stderr.map(Sink::File));
+        true
+    }
+
+    fn pop_redirects(&mut self, redirect: &Redirect) {
+        if redirect.stdin.is_some() {
+            self.stdin_files.pop();
+        }
+        if redirect.stdout.is_some() {
+            self.stdout_sinks.pop();
+        }
+        if redirect.stderr.is_some() {
+            self.stderr_sinks.pop();
+        }
+    }
+
+    fn enter_subshell(&mut self) -> SubshellState {
+        self.subshell_depth += 1;
+        SubshellState {
+            environment: self.environment.snapshot(),
+            variables: self.variables.clone(),
+            arrays: self.arrays.clone(),
+            traps: std::mem::take(&mut self.traps),
+            functions: self.functions.clone(),
+            aliases: self.aliases.clone(),
+            directories: self.directories.clone(),
+            arguments: self.arguments.clone(),
+            options: self.options.clone(),
+            locals: self.frames.iter().map(|frame| frame.locals.len()).collect(),
+        }
+    }
+
+    fn leave_subshell(&mut self, state: SubshellState) {
+        self.flow = Flow::Normal;
+        self.run_trap(signals::EXIT);
+
+        self.environment.restore(state.environment);
+        self.variables = state.variables;
+        self.arrays = state.arrays;
+        self.traps = state.traps;
+        self.functions = state.functions;
+        self.aliases = state.aliases;
+        self.directories = state.directories;
+        self.arguments = state.arguments;
+        self.options = state.options;
+        for (frame, len) in self.frames.iter_mut().zip(state.locals) {
+            frame.locals.truncate(len);
+        }
+        self.subshell_depth -= 1;
+    }
+
+    fn eval_condition(&mut self, cond: &Expr) {
+        self.condition_depth += 1;
+        self.eval_expr(cond);
+        self.condition_depth -= 1;
+    }
+
+    fn expand_arguments(&self, arguments: &[String]) -> Vec<String> {
+        arguments
+            .iter()
+            .flat_map(|argument| expand_word(argument, &|name| self.lookup(name), self.environment.base()))
+            .collect()
+    }
+
+    fn expand_redirect(&self, redirect: &Redirect) -> Redirect {
+        let expand = |filename: &Option<String>| {
+            filename.as_ref().map(|filename| expand_string(filename, &|name| self.lookup(name)))
+        };
+        Redirect::new(expand(&redirect.stdin), expand(&redirect.stdout), expand(&redirect.stderr))
+    }
+
+    fn expand_declarations(&self, arguments: &[String]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| expand_string(argument, &|name| self.lookup(name)))
+            .collect()
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        let value = self.get(name);
+        if value.is_none() && self.option("nounset") {
+            self.unbound.borrow_mut().get_or_insert_with(|| name.to_string());
+        }
+        value
+    }
+
+    fn unbound_variable(&mut self) -> bool {
+        match self.unbound.take() {
+            Some(name) => {
+                self.report(ExpandError::UnboundVariable(name));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, value: String) {
+        if !self.variables.contains_key(name) && self.environment.var(name).is_some() {
+            self.environment.set_var(name, &value);
+        } else {
+            self.variables.insert(name.to_string(), value);
+        }
+    }
+
+    fn execute(&mut self, cmd_type: &str, arguments: &[String], redirect: &Redirect) {
+        if Lexer::is_assignment(cmd_type) {
+            return self.execute_assignments(cmd_type, arguments, redirect);
+        }
+        let arguments = &match cmd_type {
+            "export" | "local" => self.expand_declarations(arguments),
+            _ => self.expand_arguments(arguments),
+        };
+        let redirect = &self.expand_redirect(redirect);
+        if self.unbound_variable() {
+            self.exit_status.push(1);
+            return;
+        }
+        self.trace(std::iter::once(cmd_type).chain(arguments.iter().map(String::as_str)));
+        if let Some(body) = self.functions.get(cmd_type).cloned() {
+            return self.call_function(cmd_type, &body, arguments, redirect);
+        }
+        self.execute_simple(cmd_type, arguments, redirect)
+    }
+
+    fn execute_assignments(&mut self, assignment: &str, arguments: &[String], redirect: &Redirect) {
+        let count = arguments.iter().take_while(|argument| Lexer::is_assignment(argument)).count();
+        let assignments = std::iter::once(assignment)
+            .chain(arguments[..count].iter().map(String::as_str))
+            .filter_map(|assignment| assignment.split_once('='))
+            .map(|(name, value)| (name.to_string(), expand_string(value, &|name| self.lookup(name))))
+            .collect::<Vec<(String, String)>>();
+        if self.unbound_variable() {
+            self.exit_status.push(1);
+            return;
+        }
+
+        match arguments[count..].split_first() {
+            Some((cmd_type, arguments)) => {
+                let saved = assignments
+                    .iter()
+                    .map(|(name, _)| (name.clone(), self.environment.var(name)))
+                    .collect::<Vec<(String, Option<String>)>>();
+                for (name, value) in &assignments {
+                    self.environment.set_var(name, value);
+                }
+                self.execute(cmd_type, arguments, redirect);
+                for (name, value) in saved {
+                    match value {
+                        Some(value) => self.environment.set_var(&name, &value),
+                        None => self.environment.remove_var(&name),
+                    }
+                }
+            }
+            None => {
+                let words = assignments.iter().map(|(name, value)| format!("{}={}", name, quote(value)));
+                self.trace(words.collect::<Vec<String>>().iter().map(String::as_str));
+                let redirect = &self.expand_redirect(redirect);
+                self.with_redirects(redirect, |interpreter| {
+                    for (name, value) in assignments {
+                        interpreter.set_variable(&name, value);
+                    }
+                    interpreter.exit_status.push(0);
+                });
+            }
+        }
+    }
+
+    fn trace<'a>(&mut self, words: impl Iterator<Item = &'a str>) {
+        if !self.option("xtrace") || self.expanding_prompt {
+            return;
+        }
+        let prompt = self.prompt("PS4");
+        let words = words.map(quote).collect::<Vec<String>>();
+        self.push_error_result(format!("{}{}", prompt, words.join(" ")));
+    }
+
+    fn check_errexit(&mut self) {
+        let failed = self.last_status() != 0;
+        if !failed || !self.option("errexit") || self.condition_depth > 0 || self.running_trap {
+            return;
+        }
+        self.request_exit(1);
+    }
+
+    pub fn prompt(&mut self, name: &str) -> String {
+        let template = self.get(name).unwrap_or_else(|| prompt::default(name).to_string());
+        let expanding_prompt = std::mem::replace(&mut self.expanding_prompt, true);
+        let prompt = prompt::expand(&template, self);
+        self.expanding_prompt = expanding_prompt;
+        prompt
+    }
+
+    pub fn run_prompt_command(&mut self) {
+        let command = match self.get("PROMPT_COMMAND") {
+            Some(command) if !command.trim().is_empty() => command,
+            _ => return,
+        };
+        let statuses = self.exit_status.clone();
+        if let Some(ast) = self.parse(&command) {
+            self.eval_expr(&ast);
+        }
+        self.flow = Flow::Normal;
+        self.exit_status = statuses;
+        self.flush_output();
+    }
+
+    fn capture_output(&mut self, command: &str) -> String {
+        let ast = match self.parse(command) {
+            Some(ast) => ast,
+            None => return String::new(),
+        };
+        let statuses = self.exit_status.clone();
+        let state = self.enter_subshell();
+        self.stdout_sinks.push(Sink::Capture(vec![]));
+        self.eval_expr(&ast);
+        let output = match self.stdout_sinks.pop() {
+            Some(Sink::Capture(output)) => String::from_utf8_lossy(&output).into_owned(),
+            _ => String::new(),
+        };
+        self.leave_subshell(state);
+        self.exit_status = statuses;
+        output.trim_end_matches('\n').to_string()
+    }
+
+    pub fn option(&self, name: &str) -> bool {
+        self.options.contains(name)
+    }
+
+    fn execute_simple(&mut self, cmd_type: &str, arguments: &[String], redirect: &Redirect) {
+        if !self.builtins.contains_key(cmd_type) {
+            return self.execute_command(cmd_type, arguments, redirect);
+        }
+        self.with_redirects(redirect, |interpreter| {
+            let status = interpreter.run_builtin(cmd_type, arguments);
+            interpreter.exit_status.push(status);
+        });
+    }
+
+    fn run_builtin(&mut self, cmd_type: &str, arguments: &[String]) -> i32 {
+        let Some(builtin) = self.builtins.get(cmd_type).cloned() else {
+            self.report(format!("{} is not a shell builtin", cmd_type));
+            return 1;
+        };
+        let mut stdin = self.builtin_input();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let status = builtin.run(self, arguments, &mut stdin, &mut stdout, &mut stderr);
+        self.push_error_output(stderr);
+        self.write_output(&stdout);
+        status.0
+    }
+
+    fn builtin_input(&mut self) -> Box<dyn Read> {
+        if let Some(input) = self.piped_input.take() {
+            return Box::new(io::Cursor::new(input));
+        }
+        match self.stdin_files.last().and_then(|file| file.try_clone().ok()) {
+            Some(file) => Box::new(file),
+            None => Box::new(io::empty()),
+        }
+    }
+
+    pub(crate) fn cd(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let mut physical = false;
+        let mut operands = arguments;
+        while let Some((option, rest)) = operands.split_first() {
+            match option.as_str() {
+                "-L" => physical = false,
+                "-P" => physical = true,
+                "--" => {
+                    operands = rest;
+                    break;
+                }
+                _ => break,
+            }
+            operands = rest;
+        }
+
+        let (directory, print) = match operands {
+            [] => match self.home() {
+                Some(home) => (home, false),
+                None => {
+                    self.report_to(streams.stderr, "HOME is not set".to_string());
+                    return false;
+                }
+            },
+            [previous] if previous == "-" => match self.environment.var("OLDPWD") {
+                Some(previous) => (previous, true),
+                None => {
+                    self.report_to(streams.stderr, "OLDPWD is not set".to_string());
+                    return false;
+                }
+            },
+            [directory] => self.search_cdpath(directory).unwrap_or((directory.clone(), false)),
+            _ => {
+                self.report_to(streams.stderr, format!("Cd expects at most one directory but found {}", operands.len()));
+                return false;
+            }
+        };
+
+        if !self.change_directory(streams.stderr, &directory, physical) {
+            return false;
+        }
+        if print {
+            streams.print(&(self.working_directory() + "\n"));
+        }
+        true
+    }
+
+    fn search_cdpath(&self, directory: &str) -> Option<(String, bool)> {
+        if !matches!(Path::new(directory).components().next(), Some(Component::Normal(_))) {
+            return None;
+        }
+        let cdpath = self.get("CDPATH")?;
+        env::split_paths(&cdpath)
+            .map(|entry| (entry.join(directory), !entry.as_os_str().is_empty()))
+            .find(|(candidate, _)| self.environment.base().join(candidate).is_dir())
+            .map(|(candidate, print)| (candidate.display().to_string(), print))
+    }
+
+    fn change_directory(&mut self, stderr: &mut dyn Write, directory: &str, physical: bool) -> bool {
+        let current = self.working_directory();
+        let logical = normalize_path(&Path::new(&current).join(directory));
+        let target = match physical {
+            true => fs::canonicalize(&logical).unwrap_or(logical),
+            false => logical,
+        };
+
+        if !target.is_dir() {
+            self.report_to(stderr, format!("{} is not a valid directory", directory));
+            return false;
+        }
+        if self.environment.set_current_dir(&target).is_err() {
+            self.report_to(stderr, "Could not set working directory".to_string());
+            return false;
+        }
+
+        self.environment.set_var("OLDPWD", &current);
+        self.environment.set_var("PWD", &target.display().to_string());
+        true
+    }
+
+    fn home(&self) -> Option<String> {
+        self.get("HOME").or_else(|| self.get("USERPROFILE")).filter(|home| !home.is_empty())
+    }
+
+    fn working_directory(&self) -> String {
+        let directory = self.environment.current_dir();
+        self.environment.var("PWD")
+            .filter(|pwd| fs::canonicalize(pwd).ok() == fs::canonicalize(&directory).ok())
+            .unwrap_or_else(|| directory.display().to_string())
+    }
+
+    fn directory_stack(&self) -> Vec<String> {
+        let mut entries = vec![self.working_directory()];
+        entries.extend(self.directories.iter().cloned());
+        entries
+    }
+
+    fn set_directory_stack(&mut self, stderr: &mut dyn Write, mut entries: Vec<String>) -> bool {
+        let top = entries.remove(0);
+        if top != self.working_directory() && !self.change_directory(stderr, &top, false) {
+            return false;
+        }
+        self.directories = entries;
+        true
+    }
+
+    fn stack_index(&mut self, stderr: &mut dyn Write, argument: &str, len: usize) -> Option<usize> {
+        let index = argument[1..].parse::<usize>().ok().filter(|index| *index < len);
+        match (index, argument.starts_with('+')) {
+            (Some(index), true) => Some(index),
+            (Some(index), false) => Some(len - 1 - index),
+            (None, _) => {
+                self.report_to(stderr, format!("{}: directory stack index out of range", argument));
+                None
+            }
+        }
+    }
+
+    pub(crate) fn pushd(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let mut entries = self.directory_stack();
+        match arguments {
+            [] if entries.len() < 2 => {
+                self.report_to(streams.stderr, "No other directory on the directory stack".to_string());
+                return false;
+            }
+            [] => entries.swap(0, 1),
+            [rotation] if is_stack_index(rotation) => match self.stack_index(streams.stderr, rotation, entries.len()) {
+                Some(index) => entries.rotate_left(index),
+                None => return false,
+            },
+            [directory] => {
+                let (directory, _) = self.search_cdpath(directory).unwrap_or((directory.clone(), false));
+                if !self.change_directory(streams.stderr, &directory, false) {
+                    return false;
+                }
+                entries.insert(0, self.working_directory());
+            }
+            _ => {
+                self.report_to(streams.stderr, format!("Pushd expects at most one directory but found {}", arguments.len()));
+                return false;
+            }
+        }
+        self.set_directory_stack(streams.stderr, entries) && self.dirs(&[], streams)
+    }
+
+    pub(crate) fn popd(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let mut entries = self.directory_stack();
+        if entries.len() < 2 {
+            self.report_to(streams.stderr, "Directory stack is empty".to_string());
+            return false;
+        }
+        match arguments {
+            [] => {
+                entries.remove(0);
+            }
+            [index] if is_stack_index(index) => match self.stack_index(streams.stderr, index, entries.len()) {
+                Some(index) => {
+                    entries.remove(index);
+                }
+                None => return false,
+            },
+            _ => {
+                self.report_to(streams.stderr, "Popd expects no argument or a directory stack index".to_string());
+                return false;
+            }
+        }
+        self.set_directory_stack(streams.stderr, entries) && self.dirs(&[], streams)
+    }
+
+    pub(crate) fn dirs(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let (mut verbose, mut lines, mut long) = (false, false, false);
+        for option in arguments {
+            match option.as_str() {
+                "-c" => self.directories.clear(),
+                "-v" => verbose = true,
+                "-p" => lines = true,
+                "-l" => long = true,
+                _ => {
+                    self.report_to(streams.stderr, format!("{} is not a valid option for dirs", option));
+                    return false;
+                }
+            }
+        }
+        if arguments.iter().any(|option| option == "-c") {
+            return true;
+        }
+
+        let home = self.home().filter(|_| !long);
+        let entries = self.directory_stack().into_iter().map(|entry| match &home {
+            Some(home) => utils::abbreviate_home(&entry, home),
+            None => entry,
+        });
+        let output = match (verbose, lines) {
+            (true, _) => entries.enumerate().map(|(index, entry)| format!("{:2}  {}\n", index, entry)).collect(),
+            (false, true) => entries.map(|entry| entry + "\n").collect(),
+            (false, false) => entries.collect::<Vec<String>>().join(" ") + "\n",
+        };
+        streams.print(&output);
+        true
+    }
+
+    pub fn exit(&mut self) -> ! {
+        self.exit_with(0)
+    }
+
+    pub(crate) fn exit_builtin(&mut self, arguments: &[String], streams: &mut Streams) -> i32 {
+        let code = match arguments.first().map(|code| code.parse::<i32>()) {
+            None => self.last_status(),
+            Some(Ok(code)) => code,
+            Some(Err(_)) => {
+                self.report_to(streams.stderr, format!("Expected a numeric exit status but found {}", arguments[0]));
+                2
+            }
+        };
+        self.request_exit(code);
+        code
+    }
+
+    fn request_exit(&mut self, code: i32) {
+        match (self.subshell_depth, self.embedded) {
+            (0, false) => self.exit_with(code),
+            (0, true) => self.exit_code = Some(code),
+            _ => (),
+        }
+        self.flow = Flow::Exit;
+    }
+
+    fn exit_with(&mut self, code: i32) -> ! {
+        self.run_trap(signals::EXIT);
+        self.error_result.iter().for_each(|x| eprintln!("{}", x));
+        self.output_result.iter().for_each(|x| println!("{}", x));
+        exit(code)
+    }
+
+    pub(crate) fn set(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        if arguments.is_empty() {
+            let mut variables = self.environment.vars().into_iter().collect::<HashMap<String, String>>();
+            variables.extend(self.variables.clone());
+            let mut names = variables.keys().cloned().collect::<Vec<String>>();
+            names.sort();
+            let output = names.iter().map(|name| format!("{}={}\n", name, quote(&variables[name]))).collect::<String>();
+            streams.print(&output);
+            return true;
+        }
+
+        let mut index = 0;
+        while let Some(argument) = arguments.get(index) {
+            if argument == "--" || argument == "-" {
+                index += 1;
+                return self.set_positional(arguments[index..].to_vec());
+            }
+            let enable = match argument.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => break,
+            };
+            for flag in argument.chars().skip(1) {
+                if flag != 'o' {
+                    match OPTIONS.iter().find(|(_, letter)| *letter == Some(flag)) {
+                        Some((name, _)) => self.set_option(name, enable),
+                        None => {
+                            self.report_to(streams.stderr, format!("{}{} is not a valid option for set", &argument[..1], flag));
+                            return false;
+                        }
+                    }
+                    continue;
+                }
+                match arguments.get(index + 1) {
+                    Some(name) => {
+                        index += 1;
+                        match OPTIONS.iter().find(|(option, _)| option == name) {
+                            Some((name, _)) => self.set_option(name, enable),
+                            None => {
+                                self.report_to(streams.stderr, format!("{} is not a valid option name", name));
+                                return false;
+                            }
+                        }
+                    }
+                    None => streams.print(&self.format_options(enable)),
+                }
+            }
+            index += 1;
+        }
+        if index < arguments.len() {
+            return self.set_positional(arguments[index..].to_vec());
+        }
+        true
+    }
+
+    fn set_option(&mut self, name: &'static str, enable: bool) {
+        match enable {
+            true => self.options.insert(name),
+            false => self.options.remove(name),
+        };
+    }
+
+    fn format_options(&self, enable: bool) -> String {
+        OPTIONS
+            .iter()
+            .map(|(name, _)| match (enable, self.option(name)) {
+                (true, true) => format!("{:<15}\ton\n", name),
+                (true, false) => format!("{:<15}\toff\n", name),
+                (false, true) => format!("set -o {}\n", name),
+                (false, false) => format!("set +o {}\n", name),
+            })
+            .collect::<String>()
+    }
+
+    fn set_positional(&mut self, arguments: Vec<String>) -> bool {
+        match self.frames.last_mut() {
+            Some(frame) => frame.arguments = arguments,
+            None => self.arguments = arguments,
+        }
+        true
+    }
+
+    pub(crate) fn export(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        if arguments.is_empty() || arguments == ["-p"] {
+            let output = self.environment.vars()
+                .iter()
+                .map(|(name, value)| format!("export {}={}\n", name, quote(value)))
+                .collect::<String>();
+            streams.print(&output);
+            return true;
+        }
+        for argument in arguments {
+            let (name, value) = match argument.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (argument.as_str(), None),
+            };
+            if !Lexer::is_assignment(&format!("{}=", name)) {
+                self.report_to(streams.stderr, format!("{} is not a valid variable name", name));
+                return false;
+            }
+            match value.or_else(|| self.variables.get(name).cloned()) {
+                Some(value) => self.environment.set_var(name, &value),
+                None => continue,
+            }
+            self.variables.remove(name);
+        }
+        true
+    }
+
+    pub(crate) fn unset(&mut self, arguments: &[String], _: &mut Streams) -> bool {
+        let (functions, names) = match arguments.split_first() {
+            Some((option, names)) if option == "-f" => (true, names),
+            Some((option, names)) if option == "-v" => (false, names),
+            _ => (false, arguments),
+        };
+        for name in names {
+            if functions {
+                self.functions.remove(name);
+                continue;
+            }
+            self.variables.remove(name);
+            self.arrays.remove(name);
+            self.environment.remove_var(name);
+        }
+        true
+    }
 
-    is_piped: bool,
+    pub(crate) fn clear(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let mut scrollback = true;
+        for option in arguments {
+            match option.as_str() {
+                "-x" => scrollback = false,
+                _ => {
+                    self.report_to(streams.stderr, format!("{} is not a valid option for clear", option));
+                    return false;
+                }
+            }
+        }
+        if !self.stdout_sinks.is_empty() || !terminal::supports_ansi() {
+            return true;
+        }
+        self.flush_output();
+        if terminal::clear(scrollback).is_err() {
+            self.report_to(streams.stderr, "Could not clear the terminal".to_string());
+            return false;
+        }
+        true
+    }
 
-    output_result: Vec<String>,
-    error_result: Vec<String>,
+    pub(crate) fn reset(&mut self, _arguments: &[String], streams: &mut Streams) -> bool {
+        let mut result = terminal::restore_modes();
+        if self.stdout_sinks.is_empty() && terminal::supports_ansi() {
+            self.flush_output();
+            result = result.and_then(|_| terminal::reset());
+        }
+        if result.is_err() {
+            self.report_to(streams.stderr, "Could not reset the terminal".to_string());
+            return false;
+        }
+        true
+    }
 
-    program_dir: String,
-}
+    fn flush_output(&mut self) {
+        self.error_result.drain(..).for_each(|x| eprintln!("{}", x));
+        self.output_result.drain(..).for_each(|x| println!("{}", x));
+    }
 
-impl Interpreter {
-    pub fn new(program_path: &str) -> Interpreter {
-        Interpreter {
-            stderr: None,
-            stdout: None,
-            exit_success: vec![],
+    pub(crate) fn pwd(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let mut physical = false;
+        for option in arguments {
+            match option.as_str() {
+                "-L" => physical = false,
+                "-P" => physical = true,
+                _ => {
+                    self.report_to(streams.stderr, format!("{} is not a valid option for pwd", option));
+                    return false;
+                }
+            }
+        }
+        let directory = match physical {
+            true => fs::canonicalize(self.working_directory()).map(|path| path.display().to_string()),
+            false => Ok(self.working_directory()),
+        };
+        match directory {
+            Ok(directory) => streams.print(&(directory + "\n")),
+            Err(_) => {
+                self.report_to(streams.stderr, "Could not read working directory".to_string());
+                return false;
+            }
+        }
+        true
+    }
 
-            is_piped: false,
+    pub(crate) fn echo(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let (mut newline, mut escapes) = (true, false);
+        let mut words = arguments;
+        while let Some((option, rest)) = words.split_first() {
+            let flags = option.strip_prefix('-').unwrap_or_default();
+            if flags.is_empty() || !flags.chars().all(|c| "neE".contains(c)) {
+                break;
+            }
+            for flag in flags.chars() {
+                match flag {
+                    'n' => newline = false,
+                    'e' => escapes = true,
+                    _ => escapes = false,
+                }
+            }
+            words = rest;
+        }
 
-            output_result: vec![],
-            error_result: vec![],
+        let mut text = words.join(" ").into_bytes();
+        if escapes {
+            let (unescaped, stop) = printf::unescape(&words.join(" "));
+            text = unescaped;
+            newline &= !stop;
+        }
+        if newline {
+            text.push(b'\n');
+        }
+        streams.write(&text);
+        true
+    }
 
-            program_dir: program_path.to_string(),
+    pub(crate) fn printf(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let (variable, arguments) = match arguments {
+            [option, name, rest @ ..] if option == "-v" => (Some(name), rest),
+            _ => (None, arguments),
+        };
+        let (format, arguments) = match arguments.split_first() {
+            Some(split) => split,
+            None => {
+                self.report_to(streams.stderr, "Printf expects a format string".to_string());
+                return false;
+            }
+        };
+        match (printf::format(format, arguments), variable) {
+            (Ok(text), Some(name)) => self.set_variable(name, String::from_utf8_lossy(&text).into_owned()),
+            (Ok(text), None) => streams.write(&text),
+            (Err(error), _) => {
+                self.report_to(streams.stderr, error);
+                return false;
+            }
         }
+        true
     }
 
-    pub fn eval(&mut self, ast: &Expr) -> (Vec<String>, Vec<String>) {
-        self.exit_success = vec![];
-        self.output_result = vec![];
-        self.error_result = vec![];
+    pub(crate) fn type_builtin(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let terse = arguments.first().is_some_and(|option| option == "-t");
+        let names = if terse { &arguments[1..] } else { arguments };
+        let mut success = !names.is_empty();
+        for name in names {
+            match self.describe(name) {
+                Some((kind, _)) if terse => streams.print(&format!("{}\n", kind)),
+                Some((_, description)) => streams.print(&(description + "\n")),
+                None => {
+                    if !terse {
+                        self.report_to(streams.stderr, format!("{} not found", name));
+                    }
+                    success = false;
+                }
+            }
+        }
+        success
+    }
 
-        self.eval_expr(ast);
-        self.process_result();
-        (self.error_result.clone(), self.output_result.clone())
+    fn describe(&self, name: &str) -> Option<(&'static str, String)> {
+        if let Some(value) = self.aliases.get(name) {
+            return Some(("alias", format!("{} is aliased to `{}'", name, value)));
+        }
+        if Lexer::is_reserved_word(name) {
+            return Some(("keyword", format!("{} is a shell keyword", name)));
+        }
+        if self.functions.contains_key(name) {
+            return Some(("function", format!("{} is a function", name)));
+        }
+        if self.builtins.contains_key(name) {
+            return Some(("builtin", format!("{} is a shell builtin", name)));
+        }
+        self.find_program(name).map(|path| ("file", format!("{} is {}", name, path)))
     }
 
-    fn eval_expr(&mut self, node: &Expr) {
-        match node {
-            Expr::Binary(lhs, Operator::Pipe, rhs) => {
-                self.eval_expr(lhs);
-                self.is_piped = true;
-                self.eval_expr(rhs);
-                self.is_piped = false;
+    fn find_program(&self, name: &str) -> Option<String> {
+        utils::find_program(&self.program_dir, name).map(|path| path.display().to_string())
+    }
+
+    pub(crate) fn command(&mut self, arguments: &[String], streams: &mut Streams) -> i32 {
+        match arguments.split_first() {
+            Some((option, names)) if option == "-v" || option == "-V" => {
+                let mut success = !names.is_empty();
+                for name in names {
+                    match (self.describe(name), option.as_str()) {
+                        (Some((_, description)), "-V") => streams.print(&(description + "\n")),
+                        (Some(("file", _)), _) => streams.print(&(self.find_program(name).unwrap_or_default() + "\n")),
+                        (Some(_), _) => streams.print(&format!("{}\n", name)),
+                        (None, _) => success = false,
+                    }
+                }
+                i32::from(!success)
             }
-            Expr::Binary(lhs, Operator::Next, rhs) => {
-                self.eval_expr(lhs);
-                self.process_result();
-                self.eval_expr(rhs);
-                self.process_result();
+            Some((name, arguments)) => {
+                self.execute_simple(name, arguments, &Redirect::new(None, None, None));
+                self.exit_status.pop().unwrap_or(0)
             }
-            Expr::Binary(lhs, Operator::NextIfSuccess, rhs) => {
-                self.eval_expr(lhs);
-                self.process_result();
-                if *self.exit_success.last().unwrap_or(&false) {
-                    self.eval_expr(rhs);
-                    self.process_result();
-                }
+            None => 0,
+        }
+    }
+
+    pub(crate) fn builtin(&mut self, arguments: &[String], streams: &mut Streams) -> i32 {
+        match arguments.split_first() {
+            Some((name, arguments)) if self.builtins.contains_key(name) => self.run_builtin(name, arguments),
+            Some((name, _)) => {
+                self.report_to(streams.stderr, format!("{} is not a shell builtin", name));
+                1
             }
-            Expr::Binary(lhs, Operator::LogicOr, rhs) => {
-                self.process_logic(lhs, rhs, |l, r| l || r)
+            None => 0,
+        }
+    }
+
+    pub(crate) fn break_builtin(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        self.break_loop(streams.stderr, arguments, Flow::Break)
+    }
+
+    pub(crate) fn continue_builtin(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        self.break_loop(streams.stderr, arguments, Flow::Continue)
+    }
+
+    pub(crate) fn bracket_test(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        match arguments.split_last() {
+            Some((last, arguments)) if last == "]" => self.test(arguments, streams),
+            _ => {
+                self.report_to(streams.stderr, "Expected ] at the end of [".to_string());
+                false
             }
-            Expr::Binary(lhs, Operator::LogicAnd, rhs) => {
-                self.process_logic(lhs, rhs, |l, r| l && r)
+        }
+    }
+
+    fn break_loop(&mut self, stderr: &mut dyn Write, arguments: &[String], flow: fn(usize) -> Flow) -> bool {
+        if self.loop_depth == 0 {
+            self.report_to(stderr, "Break and continue are only meaningful in a loop".to_string());
+            return false;
+        }
+        let count = match arguments.first().map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            Some(_) => {
+                self.report_to(stderr, format!("Expected a positive loop count but found {}", arguments[0]));
+                return false;
             }
-            Expr::If(cond, then_expr) => {
-                self.eval_expr(cond);
-                self.process_result();
-                let condition = self.exit_success.pop().unwrap_or(false);
-                if condition { self.eval_expr(then_expr) }
+        };
+        self.flow = flow(count.min(self.loop_depth));
+        true
+    }
+
+    pub(crate) fn test(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        match conditional::test(arguments, self.environment.base()) {
+            Ok(result) => result,
+            Err(e) => {
+                self.report_to(streams.stderr, e);
+                false
             }
-            Expr::IfElse(cond, then_expr, else_expr) => {
-                self.eval_expr(cond);
-                self.process_result();
-                let condition = self.exit_success.pop().unwrap_or(false);
-                self.eval_expr(if condition { then_expr } else { else_expr });
+        }
+    }
+
+    fn parse(&mut self, input: &str) -> Option<Expr> {
+        self.parse_at(input, 1)
+    }
+
+    fn parse_at(&mut self, input: &str, line: usize) -> Option<Expr> {
+        match parse_with_aliases(&(input.to_string() + "\n"), &self.program_dir, &self.aliases) {
+            Ok(expr) => Some(expr),
+            Err(mut error) => {
+                error.span.line += line - 1;
+                self.report_parse(error, input);
+                None
             }
-            Expr::Cmd { name: cmd_type, arguments, redirect } => {
-                self.execute(cmd_type, arguments, &redirect);
+        }
+    }
+
+    pub fn errors(&self) -> &[ShellError] {
+        &self.errors
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn aliases(&self) -> &HashMap<String, String> {
+        &self.aliases
+    }
+
+    pub(crate) fn alias(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        let arguments = match arguments.first().map(String::as_str) {
+            Some("-p") => &arguments[1..],
+            _ => arguments,
+        };
+        if arguments.is_empty() {
+            let mut names = self.aliases.keys().cloned().collect::<Vec<String>>();
+            names.sort();
+            let output = names.iter().map(|name| self.format_alias(name)).collect::<Vec<String>>();
+            if !output.is_empty() {
+                streams.print(&(output.join("\n") + "\n"));
+            }
+            return true;
+        }
+
+        let mut success = true;
+        for argument in arguments {
+            match argument.split_once('=') {
+                Some((name, _)) if name.is_empty() || name.contains(['/', '$', '\'', '"']) => {
+                    self.report_to(streams.stderr, format!("{} is not a valid alias name", name));
+                    success = false;
+                }
+                Some((name, value)) => {
+                    self.aliases.insert(name.to_string(), value.to_string());
+                }
+                None if self.aliases.contains_key(argument) => {
+                    streams.print(&(self.format_alias(argument) + "\n"));
+                }
+                None => {
+                    self.report_to(streams.stderr, format!("Alias {} not found", argument));
+                    success = false;
+                }
             }
         }
+        success
+    }
+
+    fn format_alias(&self, name: &str) -> String {
+        let value = self.aliases.get(name).map_or(String::new(), |value| value.replace('\'', "'\\''"));
+        format!("alias {}='{}'", name, value)
     }
 
-    fn execute(&mut self, cmd_type: &str, arguments: &Vec<String>, redirect: &Redirect) {
-        match cmd_type {
-            "cd" => self.cd(&arguments),
-            "exit" => self.exit(),
-            "set" => self.set(&arguments),
-            "clear" => self.clear(),
-            _ => self.execute_command(cmd_type, arguments, redirect)
+    pub(crate) fn unalias(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        if arguments.first().map(String::as_str) == Some("-a") {
+            self.aliases.clear();
+            return true;
         }
+        if arguments.is_empty() {
+            self.report_to(streams.stderr, "Expected an alias name or -a".to_string());
+            return false;
+        }
+        let mut success = true;
+        for name in arguments {
+            if self.aliases.remove(name).is_none() {
+                self.report_to(streams.stderr, format!("Alias {} not found", name));
+                success = false;
+            }
+        }
+        success
     }
 
-    fn cd(&mut self, arguments: &Vec<String>) {
-        let directory = match arguments.last() {
-            Some(dir) => dir,
-            _ => {
-                self.push_error_result("Cd has no argument".to_string());
-                return;
+    fn call_function(&mut self, name: &str, body: &Expr, arguments: &[String], redirect: &Redirect) {
+        if self.frames.len() >= MAX_FUNCTION_DEPTH {
+            self.report(format!("{}: maximum function nesting level of {} exceeded", name, MAX_FUNCTION_DEPTH));
+            self.exit_status.push(1);
+            return;
+        }
+
+        self.frames.push(Frame { arguments: arguments.to_vec(), locals: vec![], function: true });
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.eval_redirected(body, redirect);
+        self.loop_depth = loop_depth;
+        if self.flow == Flow::Return {
+            self.flow = Flow::Normal;
+        }
+
+        if let Some(frame) = self.frames.pop() {
+            for (name, value, env_value) in frame.locals.into_iter().rev() {
+                match value {
+                    Some(value) => self.variables.insert(name.clone(), value),
+                    None => self.variables.remove(&name),
+                };
+                match env_value {
+                    Some(value) => self.environment.set_var(&name, &value),
+                    None => self.environment.remove_var(&name),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn source(&mut self, arguments: &[String], streams: &mut Streams) -> i32 {
+        self.source_file(streams.stderr, arguments)
+    }
+
+    fn source_file(&mut self, stderr: &mut dyn Write, arguments: &[String]) -> i32 {
+        let path = match arguments.first() {
+            Some(path) => path,
+            None => {
+                self.report_to(stderr, "Source expects a file name".to_string());
+                return 1;
             }
         };
+        let script = match fs::read_to_string(self.environment.resolve(path)) {
+            Ok(script) => script,
+            Err(_) => {
+                self.report_to(stderr, format!("Could not read file: {}", path));
+                return 1;
+            }
+        };
+        if self.frames.len() >= MAX_FUNCTION_DEPTH {
+            self.report_to(stderr, format!("{}: maximum source nesting level of {} exceeded", path, MAX_FUNCTION_DEPTH));
+            return 1;
+        }
 
-        if !is_dir(directory) {
-            self.push_error_result(format!("{} is not a valid directory", directory));
-            return;
+        let arguments = match arguments.len() {
+            1 => self.positional().to_vec(),
+            _ => arguments[1..].to_vec(),
+        };
+        self.frames.push(Frame { arguments, locals: vec![], function: false });
+        let status = self.eval_script(&script);
+        self.frames.pop();
+        if self.flow == Flow::Return {
+            self.flow = Flow::Normal;
+        }
+        status
+    }
+
+    pub(crate) fn local(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        if !self.frames.iter().any(|frame| frame.function) {
+            self.report_to(streams.stderr, "Local is only meaningful in a function".to_string());
+            return false;
+        }
+        for argument in arguments {
+            let (name, value) = match argument.split_once('=') {
+                Some((name, value)) => (name, value.to_string()),
+                None => (argument.as_str(), String::new()),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                self.report_to(streams.stderr, format!("{} is not a valid variable name", name));
+                return false;
+            }
+
+            let saved = (name.to_string(), self.variables.get(name).cloned(), self.environment.var(name));
+            if let Some(frame) = self.frames.iter_mut().rev().find(|frame| frame.function) {
+                if !frame.locals.iter().any(|(local, _, _)| local == name) {
+                    frame.locals.push(saved);
+                }
+            }
+            self.variables.insert(name.to_string(), value);
+        }
+        true
+    }
+
+    pub(crate) fn return_function(&mut self, arguments: &[String], streams: &mut Streams) -> i32 {
+        if self.frames.is_empty() {
+            self.report_to(streams.stderr, "Return is only meaningful in a function or sourced file".to_string());
+            return 1;
+        }
+        let status = match arguments.first().map(|n| n.parse::<i32>()) {
+            None => self.last_status(),
+            Some(Ok(code)) => code,
+            Some(Err(_)) => {
+                self.report_to(streams.stderr, format!("Expected a numeric return value but found {}", arguments[0]));
+                2
+            }
+        };
+        self.flow = Flow::Return;
+        status
+    }
+
+    fn positional(&self) -> &[String] {
+        self.frames.last().map_or(&self.arguments, |frame| &frame.arguments)
+    }
+
+    fn array_element(&self, name: &str) -> Option<String> {
+        let (array, index) = match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+            Some((array, index)) => (array, index),
+            None => return self.arrays.get(name)?.first().cloned(),
+        };
+        let values = self.arrays.get(array)?;
+        match index {
+            "@" | "*" => Some(values.join(" ")),
+            index => values.get(index.parse::<usize>().ok()?).cloned(),
+        }
+    }
+
+    pub(crate) fn trap(&mut self, arguments: &[String], streams: &mut Streams) -> bool {
+        match arguments.first().map(String::as_str) {
+            None => self.print_traps(&[], streams),
+            Some("-p") => self.print_traps(&arguments[1..], streams),
+            Some("-") => self.set_traps(streams.stderr, &arguments[1..], None),
+            Some(command) => {
+                let command = command.to_string();
+                self.set_traps(streams.stderr, &arguments[1..], Some(command))
+            }
         }
+    }
 
-        let path = Path::new(directory);
-        match env::set_current_dir(path) {
-            Ok(_) => (),
-            Err(_) => self.push_error_result("Could set working directory".to_string())
+    fn print_traps(&mut self, specs: &[String], streams: &mut Streams) -> bool {
+        let mut names = Vec::new();
+        for spec in specs {
+            match signals::parse_spec(spec) {
+                Some(name) => names.push(name),
+                None => {
+                    self.report_to(streams.stderr, format!("{} is not a valid signal specification", spec));
+                    return false;
+                }
+            }
+        }
+        if specs.is_empty() {
+            names = signals::names();
         }
 
-        self.stdout = None;
-        self.stderr = None;
+        let listing = names
+            .iter()
+            .filter_map(|name| self.traps.get(name).map(|trap| (name, trap)))
+            .map(|(name, trap)| format!("trap -- '{}' {}", trap.command.replace('\'', "'\\''"), name))
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !listing.is_empty() {
+            streams.print(&(listing + "\n"));
+        }
+        true
     }
 
-    fn exit(&self) {
-        exit(0)
+    fn set_traps(&mut self, stderr: &mut dyn Write, specs: &[String], command: Option<String>) -> bool {
+        if specs.is_empty() {
+            self.report_to(stderr, "Trap expects at least one signal specification".to_string());
+            return false;
+        }
+
+        let handler = match &command {
+            Some(command) if !command.trim().is_empty() => match self.parse(command) {
+                Some(expr) => Some(expr),
+                None => return false,
+            },
+            _ => None,
+        };
+
+        let mut success = true;
+        for spec in specs {
+            let name = match signals::parse_spec(spec) {
+                Some(name) => name,
+                None => {
+                    self.report_to(stderr, format!("{} is not a valid signal specification", spec));
+                    success = false;
+                    continue;
+                }
+            };
+
+            match &command {
+                None => {
+                    self.traps.remove(name);
+                    if let Some(number) = signals::number(name) {
+                        signals::reset(number)
+                    }
+                }
+                Some(command) => {
+                    let trap = Trap { command: command.clone(), handler: handler.clone() };
+                    match (&trap.handler, signals::number(name)) {
+                        (Some(_), Some(number)) => signals::catch(number),
+                        (None, Some(number)) => signals::ignore(number),
+                        _ => (),
+                    }
+                    self.traps.insert(name, trap);
+                }
+            }
+        }
+        success
     }
 
-    fn set(&mut self, arguments: &Vec<String>) {
-        if arguments.len() < 2 {
-            self.push_error_result(format!("Expected at least 2 arguments but found {}", arguments.len()));
+    fn run_traps(&mut self) {
+        if self.running_trap {
             return;
         }
-        let valid_key = arguments[0].chars().into_iter().all(|c| c.is_alphabetic() || c == '_');
-        if !valid_key {
-            self.push_error_result(format!("An environment variable can only contain \
-            alphabetic characters or _ but found {}", arguments[0]));
+        let failed = self.last_status() != 0;
+        if failed && self.condition_depth == 0 {
+            self.run_trap(signals::ERR);
+        }
+        self.run_signal_traps();
+    }
+
+    fn run_signal_traps(&mut self) {
+        if self.running_trap {
             return;
         }
-        let key = arguments[0].to_string();
-        let value = arguments[1..].join(" ");
-        env::set_var(key, value)
+        for number in signals::take_pending() {
+            if let Some(name) = signals::name(number) {
+                self.run_trap(name);
+            }
+        }
     }
 
-    fn clear(&self) {
-        Command::new("powershell").arg("cls").output().unwrap();
+    fn run_trap(&mut self, name: &str) {
+        let handler = match self.traps.get(name).and_then(|trap| trap.handler.clone()) {
+            Some(handler) => handler,
+            None => return,
+        };
+
+        let status_len = self.exit_status.len();
+        self.running_trap = true;
+        self.eval_expr(&handler);
+        self.running_trap = false;
+        self.exit_status.truncate(status_len);
     }
 
-    fn execute_command(&mut self, program_name: &str, arguments: &Vec<String>, redirect: &Redirect) {
-        let program_path = self.program_dir.clone() + program_name;
+    fn execute_command(&mut self, program_name: &str, arguments: &[String], redirect: &Redirect) {
+        let Some(program_path) = self.find_program(program_name) else {
+            self.exit_status.push(1);
+            self.report(ExecError::CommandNotFound(program_name.to_string()));
+            return;
+        };
         let mut command = Command::new(&program_path);
-        command
-            .args(arguments)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        command.args(arguments);
+        self.environment.apply(&mut command);
+        if self.capture || self.stdout_sinks.last().is_some_and(Sink::is_captured) {
+            command.stdout(Stdio::piped());
+        }
+        if self.capture || self.stderr_sinks.last().is_some_and(Sink::is_captured) {
+            command.stderr(Stdio::piped());
+        }
+
+        if let Some(file) = self.stdin_files.last().and_then(|file| file.try_clone().ok()) {
+            command.stdin(file);
+        }
+        if let Some(Sink::File(file)) = self.stdout_sinks.last() {
+            if let Ok(file) = file.try_clone() {
+                command.stdout(file);
+            }
+        }
+        if let Some(Sink::File(file)) = self.stderr_sinks.last() {
+            if let Ok(file) = file.try_clone() {
+                command.stderr(file);
+            }
+        }
 
         if self.is_piped {
             command = self.pipe_prev_stdout_to_stdin(command)
@@ -172,25 +1960,60 @@ impl Interpreter {
 
         match command.spawn() {
             Ok(mut child) => {
+                if self.is_piped {
+                    if let (Some(input), Some(mut stdin)) = (self.piped_input.take(), child.stdin.take()) {
+                        thread::spawn(move || stdin.write_all(&input));
+                    }
+                }
+                let (stdout, stderr) = self.read_output(&mut child);
                 match child.wait() {
                     Ok(status) => {
-                        self.exit_success.push(status.success())
+                        self.exit_status.push(exit_code(status))
                     }
-                    Err(_) => self.push_error_result("Command was not running".to_string())
+                    Err(_) => self.report("Command was not running".to_string())
+                }
+                self.push_error_output(stderr);
+                if let Some(buffer) = stdout.filter(|buffer| !buffer.is_empty()) {
+                    self.push_output_result(buffer);
                 }
-                self.stderr = child.stderr;
-                self.stdout = child.stdout;
             }
-            Err(e) => self.push_error_result(format!("{}\r\n{}", e.to_string(), &program_path))
+            Err(e) => {
+                self.exit_status.push(1);
+                self.report(ExecError::spawn(&program_path, &e))
+            }
         };
     }
 
-    fn pipe_prev_stdout_to_stdin(&mut self, mut command: Command) -> Command {
-        match self.stdout.take() {
-            Some(stdout) => {
-                let prev_stdout = Stdio::from(stdout);
-                command.stdin(prev_stdout);
+    fn read_output(&mut self, child: &mut Child) -> (Option<Vec<u8>>, Vec<u8>) {
+        let streaming = |sinks: &[Sink]| matches!(sinks.last(), Some(Sink::Stream(..)));
+        let stream_stdout = streaming(&self.stdout_sinks);
+        let stream_stderr = streaming(&self.stderr_sinks);
+
+        let piped = child.stdout.is_some();
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward(stdout, sender.clone(), Output::Stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(stderr, sender.clone(), Output::Stderr);
+        }
+        drop(sender);
+
+        let (mut output, mut errors) = (vec![], vec![]);
+        for chunk in receiver {
+            match chunk {
+                Output::Stdout(chunk) if stream_stdout => self.push_output_result(chunk),
+                Output::Stdout(chunk) => output.extend(chunk),
+                Output::Stderr(chunk) if stream_stderr => self.push_error_output(chunk),
+                Output::Stderr(chunk) => errors.extend(chunk),
             }
+        }
+        (piped.then_some(output), errors)
+    }
+
+    fn pipe_prev_stdout_to_stdin(&mut self, mut command: Command) -> Command {
+        match self.piped_input {
+            Some(_) => { command.stdin(Stdio::piped()); }
             None => { command.stdin(Stdio::null()); }
         }
         command
@@ -201,14 +2024,14 @@ impl Interpreter {
             command.stdin(Stdio::null());
             return command;
         }
-        if !Path::new(filename).is_file() {
-            self.push_error_result(format!("File does not exist: {}", filename));
+        if !self.environment.resolve(filename).is_file() {
+            self.report(ExecError::RedirectInput { path: filename.to_string(), kind: ErrorKind::NotFound });
             command.stdin(Stdio::null());
             return command;
         }
-        match File::open(filename) {
+        match File::open(self.environment.resolve(filename)) {
             Ok(file) => { command.stdin(file); }
-            Err(_) => self.push_error_result(format!("Could not read file: {}", filename))
+            Err(e) => self.report(ExecError::RedirectInput { path: filename.to_string(), kind: e.kind() })
         };
         command
     }
@@ -218,9 +2041,9 @@ impl Interpreter {
             command.stdout(Stdio::null());
             return command;
         }
-        match File::create(filename) {
+        match File::create(self.environment.resolve(filename)) {
             Ok(file) => { command.stdout(file); }
-            Err(_) => self.push_error_result(format!("Could not create file: {}", filename))
+            Err(e) => self.report(ExecError::RedirectOutput { path: filename.to_string(), kind: e.kind() })
         };
         command
     }
@@ -230,49 +2053,163 @@ impl Interpreter {
             command.stderr(Stdio::null());
             return command;
         }
-        match File::create(filename) {
+        match File::create(self.environment.resolve(filename)) {
             Ok(file) => { command.stderr(file); }
-            Err(_) => self.push_error_result(format!("Could not create file: {}", filename))
+            Err(e) => self.report(ExecError::RedirectOutput { path: filename.to_string(), kind: e.kind() })
         };
         command
     }
 
-    fn process_result(&mut self) {
-        if let Some(mut stderr) = self.stderr.take() {
-            if let Ok(buffer) = self.get_buffer(&mut stderr as &mut dyn Read) {
-                self.push_error_result(buffer);
+    fn push_error_output(&mut self, buffer: Vec<u8>) {
+        match self.stderr_sinks.last_mut() {
+            _ if buffer.is_empty() => (),
+            Some(sink) if sink.is_captured() => sink.write(&buffer),
+            _ => self.push_error_result(String::from_utf8_lossy(&buffer).into_owned()),
+        }
+    }
+
+    fn push_output_result(&mut self, buffer: Vec<u8>) {
+        match self.stdout_sinks.last_mut() {
+            Some(sink) => sink.write(&buffer),
+            None if self.capture => self.output_result.push(String::from_utf8_lossy(&buffer).trim().to_string()),
+            None => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&buffer).and_then(|_| stdout.flush());
             }
         }
-        if let Some(mut stdout) = self.stdout.take() {
-            if let Ok(buffer) = self.get_buffer(&mut stdout as &mut dyn Read) {
-                self.push_output_result(buffer);
+    }
+
+    fn write_output(&mut self, buffer: &[u8]) {
+        if buffer.is_empty() {
+            return;
+        }
+        match self.stdout_sinks.last_mut() {
+            Some(sink) => sink.write(buffer),
+            None if self.capture => {
+                let text = String::from_utf8_lossy(buffer);
+                self.output_result.push(text.strip_suffix('\n').unwrap_or(&text).to_string())
+            }
+            None => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(buffer).and_then(|_| stdout.flush());
             }
         }
     }
 
-    fn get_buffer(&mut self, fd: &mut dyn Read) -> Result<String, ()> {
-        let mut buffer = String::new();
-        fd.read_to_string(&mut buffer).expect("Could not read buffer from file descriptor");
-        if !buffer.is_empty() { Ok(buffer) } else { Err(()) }
+    pub(crate) fn report(&mut self, error: impl Into<ShellError>) {
+        let error = error.into();
+        self.push_error_result(error.to_string());
+        self.errors.push(error);
     }
 
-    fn push_output_result(&mut self, buffer: String) {
-        self.output_result.push(buffer.trim().to_string())
+    pub(crate) fn report_to(&mut self, stderr: &mut dyn Write, error: impl Into<ShellError>) {
+        let error = error.into();
+        let _ = writeln!(stderr, "{}", error);
+        self.errors.push(error);
+    }
+
+    fn report_parse(&mut self, error: ParseError, source: &str) {
+        self.push_error_result(error.render(source));
+        self.errors.push(ShellError::Parse(error));
     }
 
     fn push_error_result(&mut self, buffer: String) {
-        self.error_result.push(buffer.trim().to_string())
+        match self.stderr_sinks.last_mut() {
+            Some(Sink::File(file)) => { let _ = writeln!(file, "{}", buffer.trim()); }
+            Some(sink) => sink.write(format!("{}\n", buffer.trim()).as_bytes()),
+            None if self.capture => self.error_result.push(buffer.trim().to_string()),
+            None => eprintln!("{}", buffer.trim())
+        }
     }
 
-    fn process_logic(&mut self, lhs: &Expr, rhs: &Expr, logic: fn(bool, bool) -> bool) {
-        self.eval_expr(lhs);
-        self.process_result();
-        let left = self.exit_success.pop().unwrap_or(false);
+    fn process_logic(&mut self, lhs: &Expr, rhs: &Expr, continue_if: bool) {
+        self.eval_condition(lhs);
+        let left = self.exit_status.pop().unwrap_or(0);
+        self.exit_status.push(left);
+        if (left == 0) != continue_if {
+            return;
+        }
 
         self.eval_expr(rhs);
-        self.process_result();
-        let right = self.exit_success.pop().unwrap_or(false);
+        let right = self.exit_status.pop().unwrap_or(0);
+        self.exit_status.push(right);
+    }
+}
+
+impl Variables for Interpreter {
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status().to_string()),
+            "$" => Some(std::process::id().to_string()),
+            "#" => Some(self.positional().len().to_string()),
+            _ if name.starts_with('#') => {
+                let name = &name[1..];
+                let length = match name.strip_suffix("[@]").or_else(|| name.strip_suffix("[*]")) {
+                    Some(array) => self.arrays.get(array).map_or(0, Vec::len),
+                    None => self.get(name).unwrap_or_default().chars().count(),
+                };
+                Some(length.to_string())
+            }
+            "@" | "*" => Some(self.positional().join(" ")),
+            _ => match name.parse::<usize>() {
+                Ok(0) => env::args().next(),
+                Ok(n) => self.positional().get(n - 1).cloned(),
+                Err(_) => self.array_element(name)
+                    .or_else(|| self.variables.get(name).cloned())
+                    .or_else(|| self.environment.var(name)),
+            },
+        }
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.set_variable(name, value)
+    }
+}
+
+impl prompt::Context for Interpreter {
+    fn status(&self) -> i32 {
+        self.last_status()
+    }
 
-        self.exit_success.push(logic(left, right));
+    fn working_directory(&self) -> String {
+        Interpreter::working_directory(self)
     }
-}
\ No newline at end of file
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.get(name)
+    }
+
+    fn substitute(&mut self, command: &str) -> String {
+        self.capture_output(command)
+    }
+}
+
+fn is_stack_index(argument: &str) -> bool {
+    argument.len() > 1 && argument.starts_with(['+', '-']) && argument[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+fn forward(mut reader: impl Read + Send + 'static, sender: mpsc::Sender<Output>, wrap: fn(Vec<u8>) -> Output) {
+    thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => {
+                    if sender.send(wrap(buffer[..count].to_vec())).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    });
+}
@@ -1,39 +1,149 @@
+use std::collections::{HashMap, VecDeque};
 use std::iter::Peekable;
-use std::path::Path;
 use std::str::Chars;
 
-use crate::token::Token;
+use crate::builtins;
+use crate::token::{Span, SpannedToken, Token};
+use crate::utils::find_program;
 
 pub struct Lexer<'input> {
     input: Peekable<Chars<'input>>,
+    injected: VecDeque<char>,
+    offset: usize,
+    line: usize,
+    column: usize,
 
     program_dir: String,
+    command_position: bool,
+    keyword_position: bool,
+    after_time: bool,
+    after_function: bool,
+    after_case: bool,
+    case_subject: bool,
+    after_for: bool,
+    ifs: Vec<(Token, bool)>,
+
+    aliases: HashMap<String, String>,
+    expansions: Vec<(String, usize, bool)>,
+    alias_next: bool,
 }
 
 impl<'input> Lexer<'input> {
     pub fn new(input: &'input str, program_dir: &str) -> Lexer<'input> {
         Lexer {
             input: input.chars().peekable(),
+            injected: VecDeque::new(),
+            offset: 0,
+            line: 1,
+            column: 1,
 
             program_dir: program_dir.to_string(),
+            command_position: true,
+            keyword_position: true,
+            after_time: false,
+            after_function: false,
+            after_case: false,
+            case_subject: false,
+            after_for: false,
+            ifs: vec![],
+
+            aliases: HashMap::new(),
+            expansions: vec![],
+            alias_next: false,
         }
     }
 
+    pub fn with_aliases(mut self, aliases: &HashMap<String, String>) -> Lexer<'input> {
+        self.aliases = aliases.clone();
+        self
+    }
+
     pub fn get_tokens(&mut self) -> Vec<Token> {
         let mut tokens = Vec::<Token>::new();
         self.for_each(|token| tokens.push(token));
         tokens
     }
 
+    pub fn get_spanned_tokens(&mut self) -> Vec<SpannedToken> {
+        std::iter::from_fn(|| self.next_spanned()).collect()
+    }
+
+    pub fn next_spanned(&mut self) -> Option<SpannedToken> {
+        let start = self.injected.len();
+        self.alias_next = self.expansions.iter().any(|(_, end, trailing)| *end >= start && *trailing);
+        self.expansions.retain(|(_, end, _)| *end < start);
+
+        self.consume_whitespaces();
+        let (offset, line, column) = (self.offset, self.line, self.column);
+        let token = self.next_token()?;
+        let span = Span { start: offset, end: self.offset.max(offset), line, column };
+
+        let time_option = self.after_time && token == Token::Hyphen("-p".to_string());
+        let assignment = matches!(&token, Token::Argument(word) if self.command_position && Self::is_assignment(word));
+        self.command_position = Self::starts_command(&token) || time_option || assignment;
+        let case_in = self.case_subject && token == Token::Argument("in".to_string());
+        self.keyword_position =
+            self.command_position || self.after_function || self.after_for || case_in || Self::ends_pattern(&token);
+        self.after_time = token == Token::Time;
+        self.after_function = token == Token::Function;
+        self.case_subject = self.after_case;
+        self.after_case = token == Token::Case;
+        self.after_for = token == Token::For;
+        self.track_if(&token);
+        self.consume_whitespaces();
+        Some(SpannedToken { token, span })
+    }
+
+    fn track_if(&mut self, token: &Token) {
+        if let Some((part, one_line)) = self.ifs.last_mut() {
+            match token {
+                Token::Semicolon | Token::EOL if matches!(part, Token::If | Token::Elif) => *one_line = false,
+                Token::Then | Token::Elif | Token::Else => *part = token.clone(),
+                _ => (),
+            }
+        }
+        match token {
+            Token::If => self.ifs.push((Token::If, true)),
+            Token::Fi => {
+                self.ifs.pop();
+            }
+            Token::DoubleSemicolon if self.ifs.last().is_some_and(|(_, one_line)| *one_line) => {
+                self.ifs.pop();
+            }
+            Token::EOL => {
+                while self.ifs.last().is_some_and(|(_, one_line)| *one_line) {
+                    self.ifs.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+
     fn next_char(&mut self) -> Option<char> {
-        self.input.next()
+        if let Some(c) = self.injected.pop_front() {
+            return Some(c);
+        }
+        let c = self.input.next()?;
+        self.offset += c.len_utf8();
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+        Some(c)
     }
 
     fn next_word(&mut self, init: String) -> String {
+        self.read_word(init).0
+    }
+
+    fn next_option(&mut self, init: String) -> String {
         let mut res = init;
         while let Some(c) = self.peek() {
             if self.is_word_member(c) {
-                res.push(self.next_char().unwrap())
+                res.push(self.next_char().unwrap());
             } else {
                 break;
             }
@@ -41,12 +151,144 @@ impl<'input> Lexer<'input> {
         res
     }
 
+    fn read_word(&mut self, init: String) -> (String, bool) {
+        let mut res = init;
+        let mut quoted = false;
+        while let Some(c) = self.peek() {
+            if Self::is_quote(c) {
+                self.next_char();
+                self.read_quoted(c, &mut res);
+                quoted = true;
+            } else if self.is_word_member(c) || c == '-' || c == '=' {
+                res.push(self.next_char().unwrap());
+            } else {
+                break;
+            }
+        }
+        (res, quoted)
+    }
+
+    fn read_quoted(&mut self, quote: char, res: &mut String) {
+        res.push(quote);
+        while let Some(c) = self.next_char() {
+            res.push(c);
+            if c == quote {
+                break;
+            }
+        }
+    }
+
+    fn read_arithmetic(&mut self) -> String {
+        let mut res = String::new();
+        let mut depth = 0;
+        while let Some(c) = self.next_char() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 && self.peek() == Some(')') => {
+                    self.next_char();
+                    break;
+                }
+                ')' => depth -= 1,
+                _ => (),
+            }
+            res.push(c);
+        }
+        res.trim().to_string()
+    }
+
+    fn read_conditional(&mut self) -> String {
+        let mut res = String::new();
+        while let Some(c) = self.next_char() {
+            if Self::is_quote(c) {
+                self.read_quoted(c, &mut res);
+                continue;
+            }
+            if c == ']' && self.peek() == Some(']') {
+                self.next_char();
+                break;
+            }
+            res.push(c);
+        }
+        res.trim().to_string()
+    }
+
+    fn is_quote(c: char) -> bool {
+        matches!(c, '\'' | '"')
+    }
+
     fn is_word_member(&self, c: char) -> bool {
-        !matches!(c, ' ' | '>' | '<' | '&' | '|' | '=' | '"' | '$' | '-' | ';' | '\r' | '\n')
+        !matches!(c, ' ' | '>' | '<' | '&' | '|' | '=' | '"' | '-' | ';' | '(' | ')' | '\r' | '\n')
+    }
+
+    pub fn is_assignment(word: &str) -> bool {
+        match word.split_once('=') {
+            Some((name, _)) => {
+                name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_reserved_word(word: &str) -> bool {
+        Self::keyword(word).is_some() || matches!(word, "!" | "time" | "[[" | "]]")
+    }
+
+    fn keyword(word: &str) -> Option<Token> {
+        match word {
+            "if" => Some(Token::If),
+            "then" => Some(Token::Then),
+            "elif" => Some(Token::Elif),
+            "else" => Some(Token::Else),
+            "fi" => Some(Token::Fi),
+            "while" => Some(Token::While),
+            "until" => Some(Token::Until),
+            "for" => Some(Token::For),
+            "do" => Some(Token::Do),
+            "done" => Some(Token::Done),
+            "case" => Some(Token::Case),
+            "esac" => Some(Token::Esac),
+            "function" => Some(Token::Function),
+            "{" => Some(Token::LeftBrace),
+            "}" => Some(Token::RightBrace),
+            _ => None,
+        }
+    }
+
+    fn ends_pattern(token: &Token) -> bool {
+        matches!(
+            token,
+            Token::RightParen | Token::DoubleSemicolon | Token::SemicolonAmpersand | Token::DoubleSemicolonAmpersand
+        )
+    }
+
+    fn starts_command(token: &Token) -> bool {
+        !matches!(
+            token,
+            Token::Command(_) | Token::Argument(_) | Token::Hyphen(_) | Token::DoubleHyphen(_) |
+            Token::EnvVariable(_) | Token::InputRedirect(_) | Token::OutputRedirect(_) |
+            Token::ErrorRedirect(_) | Token::For | Token::Case | Token::RightParen | Token::RightBrace |
+            Token::Done | Token::Esac | Token::Fi | Token::DoubleSemicolon | Token::SemicolonAmpersand |
+            Token::DoubleSemicolonAmpersand | Token::Conditional(_)
+        )
     }
 
     fn peek(&mut self) -> Option<char> {
-        self.input.peek().cloned()
+        self.injected.front().cloned().or_else(|| self.input.peek().cloned())
+    }
+
+    fn expand_alias(&mut self, word: &str) -> bool {
+        let (name, value) = match self.aliases.get_key_value(word) {
+            Some((name, value)) => (name.clone(), value.clone()),
+            None => return false,
+        };
+        if self.expansions.iter().any(|(active, _, _)| *active == name) {
+            return false;
+        }
+        self.expansions.push((name, self.injected.len(), value.ends_with(' ')));
+        value.trim_start().chars().rev().for_each(|c| self.injected.push_front(c));
+        self.alias_next = true;
+        true
     }
 
     fn consume_whitespaces(&mut self) {
@@ -58,13 +300,9 @@ impl<'input> Lexer<'input> {
             }
         }
     }
-}
 
-impl<'input> Iterator for Lexer<'input> {
-    type Item = Token;
-
-    fn next(&mut self) -> Option<Token> {
-        let token = match self.next_char() {
+    fn next_token(&mut self) -> Option<Token> {
+        match self.next_char() {
             Some('&') =>
                 if self.peek() == Some('&') {
                     self.next_char();
@@ -84,11 +322,33 @@ impl<'input> Iterator for Lexer<'input> {
             Some(';') =>
                 if self.peek() == Some(';') {
                     self.next_char();
-                    Some(Token::DoubleSemicolon)
+                    if self.peek() == Some('&') {
+                        self.next_char();
+                        Some(Token::DoubleSemicolonAmpersand)
+                    } else {
+                        Some(Token::DoubleSemicolon)
+                    }
+                } else if self.peek() == Some('&') {
+                    self.next_char();
+                    Some(Token::SemicolonAmpersand)
                 } else {
                     Some(Token::Semicolon)
                 },
 
+            Some('(') if self.peek() == Some('(') => {
+                self.next_char();
+                Some(Token::Arithmetic(self.read_arithmetic()))
+            }
+
+            Some('[') if self.command_position && self.peek() == Some('[') => {
+                self.next_char();
+                Some(Token::Conditional(self.read_conditional()))
+            }
+
+            Some('(') => Some(Token::LeftParen),
+
+            Some(')') => Some(Token::RightParen),
+
             Some('$') => {
                 let res = self.next_word("".to_string());
                 Some(Token::EnvVariable(res))
@@ -97,14 +357,21 @@ impl<'input> Iterator for Lexer<'input> {
             Some('-') => {
                 if self.peek() == Some('-') {
                     self.next_char();
-                    let option = self.next_word("--".to_string());
+                    let option = self.next_option("--".to_string());
                     Some(Token::DoubleHyphen(option))
                 } else {
-                    let option = self.next_word("-".to_string());
+                    let option = self.next_option("-".to_string());
                     Some(Token::Hyphen(option))
                 }
             }
 
+            Some('#') => {
+                while self.peek().is_some_and(|c| c != '\n' && c != '\r') {
+                    self.next_char();
+                }
+                self.next_token()
+            }
+
             Some('\n') => Some(Token::EOL),
 
             Some('\r') => {
@@ -142,33 +409,60 @@ impl<'input> Iterator for Lexer<'input> {
                 Some(Token::ErrorRedirect(res))
             }
 
+            Some(c) if Self::is_quote(c) => {
+                let mut init = String::new();
+                self.read_quoted(c, &mut init);
+                let (word, _) = self.read_word(init);
+                Some(Token::Argument(word))
+            }
+
             Some(c) => Some({
-                let word = self.next_word(c.to_string()).to_lowercase();
+                let (word, quoted) = self.read_word(c.to_string());
+                if quoted || (self.command_position && Self::is_assignment(&word)) {
+                    return Some(Token::Argument(word));
+                }
 
-                if word == "if" {
-                    self.consume_whitespaces();
-                    return Some(Token::If);
+                let lowercase = word.to_lowercase();
+                if self.command_position {
+                    match lowercase.as_str() {
+                        "!" => return Some(Token::Bang),
+                        "time" => return Some(Token::Time),
+                        _ => (),
+                    }
+                }
+                let separator_free = matches!((lowercase.as_str(), self.ifs.last()),
+                    ("then", Some((Token::If | Token::Elif, _))) | ("else", Some((Token::Then, _))));
+                if let Some(keyword) = Self::keyword(&lowercase).filter(|_| self.keyword_position || separator_free) {
+                    return Some(keyword);
                 }
-                if word == "then" {
-                    self.consume_whitespaces();
-                    return Some(Token::Then);
+                if (self.command_position || self.alias_next) && self.expand_alias(&word) {
+                    return self.next_token();
                 }
-                if word == "else" {
-                    self.consume_whitespaces();
-                    return Some(Token::Else);
+
+                if !self.command_position {
+                    return Some(Token::Argument(word));
                 }
 
-                let program_path = self.program_dir.clone() + &word + ".exe";
-                let built_in_shell = ["cd", "exit", "set", "clear"].contains(&word.as_str());
-                let found_program = Path::new(&program_path).is_file();
+                let built_in_shell = builtins::is_internal(&lowercase);
+                let found_program = find_program(&self.program_dir, &lowercase).is_some();
                 let program_exists = found_program || built_in_shell;
 
-                if program_exists { Token::Command(word) } else { Token::Argument(word) }
+                if program_exists {
+                    Token::Command(lowercase)
+                } else {
+                    Token::Argument(lowercase)
+                }
             }),
 
             None => None
-        };
-        self.consume_whitespaces();
-        token
+        }
     }
-}
\ No newline at end of file
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.next_spanned().map(|spanned| spanned.token)
+    }
+}
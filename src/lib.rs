@@ -4,4 +4,18 @@ pub mod parser;
 pub mod ast;
 pub mod interpreter;
 pub mod config;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod signals;
+pub mod expand;
+pub mod arithmetic;
+pub mod glob;
+pub mod timing;
+pub mod conditional;
+pub mod printf;
+pub mod terminal;
+pub mod prompt;
+pub mod error;
+pub mod environment;
+pub mod shell;
+pub mod builtins;
+pub mod help;
@@ -1,65 +1,171 @@
-use std::env;
-use std::iter::Peekable;
-
-use crate::ast::{Expr, Operator, Redirect};
+use crate::ast::{CaseArm, CaseTerminator, Expr, Operator, Redirect};
+use crate::conditional;
+use crate::error::ParseError;
 use crate::lexer::Lexer;
-use crate::token::Token;
+use crate::token::{Span, SpannedToken, Token};
 
 pub struct Parser<'lexer> {
-    lexer: Peekable<Lexer<'lexer>>,
+    lexer: Lexer<'lexer>,
+    peeked: Option<Option<SpannedToken>>,
+    span: Span,
+    multiline: bool,
+    after_separator: bool,
 }
 
 impl<'lexer> Parser<'lexer> {
     pub fn new(lexer: Lexer) -> Parser {
         Parser {
-            lexer: lexer.peekable(),
+            lexer,
+            peeked: None,
+            span: Span::default(),
+            multiline: true,
+            after_separator: false,
         }
     }
 
     fn next(&mut self) -> Option<Token> {
-        self.lexer.next()
+        let spanned = match self.peeked.take() {
+            Some(spanned) => spanned,
+            None => self.lexer.next_spanned(),
+        };
+        let token = spanned.map(|spanned| {
+            self.span = spanned.span;
+            spanned.token
+        });
+        self.after_separator = matches!(token, Some(Token::Semicolon | Token::EOL));
+        token
+    }
+
+    pub fn parse(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_block(true)?;
+        self.skip_newlines();
+        match self.peek().cloned() {
+            None => Ok(expr),
+            Some(token) => Err(self.error_ahead(format!("Unexpected {}", token))),
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Expr, String> {
-        self.parse_expr(0)
+    pub fn at_end(&mut self) -> bool {
+        self.skip_newlines();
+        self.peek().is_none()
     }
 
     fn peek(&mut self) -> Option<&Token> {
-        self.lexer.peek().take()
+        let lexer = &mut self.lexer;
+        self.peeked.get_or_insert_with(|| lexer.next_spanned()).as_ref().map(|spanned| &spanned.token)
     }
 
-    fn parse_expr(&mut self, min_binding_power: u8) -> Result<Expr, String> {
+    fn peek_span(&mut self) -> Span {
+        self.peek();
+        match &self.peeked {
+            Some(Some(spanned)) => spanned.span,
+            _ => Span { start: self.span.end, end: self.span.end, ..self.span },
+        }
+    }
+
+    fn error(&self, message: String, found: Option<Token>) -> ParseError {
+        ParseError { message, span: self.span, expected: vec![], found }
+    }
+
+    fn error_ahead(&mut self, message: String) -> ParseError {
+        let span = self.peek_span();
+        let found = self.peek().cloned();
+        ParseError { message, span, expected: vec![], found }
+    }
+
+    fn parse_expr(&mut self, min_binding_power: u8) -> Result<Expr, ParseError> {
         let mut lhs = self.parse_atom()?;
-        loop {
-            let op;
-            match self.peek_operator() {
-                Ok(o) => op = o,
-                Err(_) => break
-            }
+        while let Ok(op) = self.peek_operator() {
 
             let (left_bp, right_bp) = self.get_binding_power(&op);
             if min_binding_power > left_bp {
                 break;
             }
             self.next();
+            if self.multiline {
+                self.skip_newlines();
+            }
+            if op == Operator::Next && self.at_terminator() {
+                break;
+            }
             let rhs = self.parse_expr(right_bp)?;
             lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
         }
         Ok(lhs)
     }
 
-    fn parse_atom(&mut self) -> Result<Expr, String> {
+    fn parse_block(&mut self, multiline: bool) -> Result<Expr, ParseError> {
+        let outer = std::mem::replace(&mut self.multiline, multiline);
+        if multiline {
+            self.skip_newlines();
+        }
+        let expr = self.parse_expr(0);
+        self.multiline = outer;
+        expr
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::EOL) {
+            self.next();
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, ParseError> {
         match self.next() {
-            Some(Token::Command(cmd_type)) => self.parse_command(&cmd_type),
+            Some(Token::Command(name) | Token::Argument(name)) if self.peek() == Some(&Token::LeftParen) => {
+                self.next();
+                self.expect(vec![Token::RightParen])?;
+                self.parse_function_body(name)
+            }
+            Some(Token::Command(cmd_type) | Token::Argument(cmd_type)) => self.parse_command(&cmd_type),
+            Some(Token::Function) => self.parse_function(),
+            Some(Token::Arithmetic(expression)) => Ok(Expr::Arithmetic(expression.trim().to_string())),
+            Some(Token::Conditional(text)) => match conditional::parse(&text) {
+                Ok(conditional) => Ok(Expr::Conditional(conditional)),
+                Err(message) => Err(self.error(message, Some(Token::Conditional(text)))),
+            },
+            Some(Token::Bang) => Ok(Expr::Not(Box::new(self.parse_pipeline()?))),
+            Some(Token::Time) => {
+                let posix = self.peek() == Some(&Token::Hyphen("-p".to_string()));
+                if posix {
+                    self.next();
+                }
+                Ok(Expr::Time(Box::new(self.parse_pipeline()?), posix))
+            }
             Some(Token::If) => self.parse_if(),
-            Some(token) => Err(format!("Expected a command or if but found {}", token)),
-            None => Err("Expected a command or if but found nothing".to_string()),
+            Some(Token::While) => {
+                let (cond, body) = self.parse_loop()?;
+                Ok(Expr::While(Box::new(cond), Box::new(body)))
+            }
+            Some(Token::Until) => {
+                let (cond, body) = self.parse_loop()?;
+                Ok(Expr::Until(Box::new(cond), Box::new(body)))
+            }
+            Some(Token::For) => self.parse_for(),
+            Some(Token::Case) => self.parse_case(),
+            Some(Token::LeftParen) => {
+                let (body, redirect) = self.parse_compound(Token::RightParen)?;
+                Ok(Expr::Subshell(Box::new(body), redirect))
+            }
+            Some(Token::LeftBrace) => {
+                let (body, redirect) = self.parse_compound(Token::RightBrace)?;
+                Ok(Expr::Group(Box::new(body), redirect))
+            }
+            Some(token) => Err(self.error(format!("Expected a command or if but found {}", token), Some(token))),
+            None => Err(self.error_ahead("Expected a command or if but found nothing".to_string())),
         }
     }
 
+    fn parse_pipeline(&mut self) -> Result<Expr, ParseError> {
+        let (left_bp, _) = self.get_binding_power(&Operator::Pipe);
+        self.parse_expr(left_bp)
+    }
+
     fn peek_operator(&mut self) -> Result<Operator, ()> {
+        let multiline = self.multiline;
         match self.peek() {
             Some(Token::Semicolon) => Ok(Operator::Next),
+            Some(Token::EOL) if multiline => Ok(Operator::Next),
             Some(Token::Pipe) => Ok(Operator::Pipe),
             Some(Token::Ampersand) => Ok(Operator::NextIfSuccess),
             Some(Token::DoubleAmpersand) => Ok(Operator::LogicAnd),
@@ -68,6 +174,17 @@ impl<'lexer> Parser<'lexer> {
         }
     }
 
+    fn at_terminator(&mut self) -> bool {
+        matches!(
+            self.peek(),
+            None | Some(
+                Token::EOL | Token::RightParen | Token::RightBrace | Token::Then | Token::Elif | Token::Else |
+                Token::Fi | Token::Do | Token::Done | Token::Esac | Token::DoubleSemicolon | Token::SemicolonAmpersand |
+                Token::DoubleSemicolonAmpersand
+            )
+        )
+    }
+
     fn get_binding_power(&mut self, op: &Operator) -> (u8, u8) {
         match op {
             Operator::Next => (1, 2),
@@ -78,65 +195,247 @@ impl<'lexer> Parser<'lexer> {
         }
     }
 
-    fn parse_command(&mut self, cmd_type: &str) -> Result<Expr, String> {
+    fn parse_command(&mut self, cmd_type: &str) -> Result<Expr, ParseError> {
         let mut arguments = Vec::new();
         let mut redirect = Redirect::new(None, None, None);
 
-        while let Some(x) = self.peek() {
-            match x {
-                Token::Command(arg) |
-                Token::Hyphen(arg) |
-                Token::DoubleHyphen(arg) |
-                Token::Argument(arg) => {
-                    arguments.push(arg.to_string());
-                    self.next();
+        loop {
+            match self.next_word() {
+                Some(argument) => arguments.push(argument),
+                None => if !self.parse_redirects(&mut redirect)? { break }
+            }
+        }
+        Ok(Expr::Cmd {
+            name: cmd_type.to_string(),
+            arguments,
+            redirect,
+        })
+    }
+
+    fn parse_loop(&mut self) -> Result<(Expr, Expr), ParseError> {
+        let cond = self.parse_block(true)?;
+        let body = self.parse_do_block()?;
+        Ok((cond, body))
+    }
+
+    fn parse_for(&mut self) -> Result<Expr, ParseError> {
+        let variable = match self.next() {
+            Some(Token::Arithmetic(header)) => {
+                let parts = header.split(';').map(|part| part.trim().to_string()).collect::<Vec<String>>();
+                if parts.len() != 3 {
+                    let message = format!("Expected three expressions in for (( )) but found: {}", header);
+                    return Err(self.error(message, Some(Token::Arithmetic(header))));
                 }
-                Token::EnvVariable(env) => {
-                    match env::var(env) {
-                        Ok(arg) => { arguments.push(arg); }
-                        Err(_) => return Err(format!("{} is not a valid environment variable", env))
-                    }
-                    self.next();
+                self.skip_separator();
+                let body = self.parse_do_block()?;
+                return Ok(Expr::ArithmeticFor(
+                    parts[0].clone(),
+                    parts[1].clone(),
+                    parts[2].clone(),
+                    Box::new(body),
+                ));
+            }
+            Some(Token::Argument(name) | Token::Command(name)) => name,
+            Some(token) => return Err(self.error(format!("Expected a variable name after for but found {}", token), Some(token))),
+            None => return Err(self.error_ahead("Expected a variable name after for but found nothing".to_string())),
+        };
+
+        let words = match self.peek() {
+            Some(Token::Argument(word)) if word == "in" => {
+                self.next();
+                let mut words = Vec::new();
+                while let Some(word) = self.next_word() {
+                    words.push(word);
                 }
-                Token::InputRedirect(filename) => {
-                    if redirect.stdin.is_some() {
-                        return Err("Only one input redirection per command is allowed".to_string());
+                Some(words)
+            }
+            Some(Token::Semicolon | Token::EOL | Token::Do) => None,
+            Some(token) => {
+                let token = token.clone();
+                return Err(self.error_ahead(format!("Expected in, ; or do but found {}", token)));
+            }
+            None => return Err(self.error_ahead("Expected in, ; or do but found nothing".to_string())),
+        };
+        self.skip_separator();
+        let body = self.parse_do_block()?;
+        Ok(Expr::For(variable, words, Box::new(body)))
+    }
+
+    fn parse_case(&mut self) -> Result<Expr, ParseError> {
+        let word = match self.next_word() {
+            Some(word) => word,
+            None => {
+                let found = self.peek().cloned();
+                return Err(self.error_ahead(format!("Expected a word after case but found {:?}", found)));
+            }
+        };
+        match self.next() {
+            Some(Token::Argument(word)) if word == "in" => (),
+            Some(token) => return Err(self.error(format!("Expected in but found {}", token), Some(token))),
+            None => return Err(self.error_ahead("Expected in but found nothing".to_string())),
+        }
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_separator();
+            if self.peek() == Some(&Token::Esac) {
+                self.next();
+                break;
+            }
+            if self.peek() == Some(&Token::LeftParen) {
+                self.next();
+            }
+
+            let mut patterns = Vec::new();
+            loop {
+                match self.next_word() {
+                    Some(pattern) => patterns.push(pattern),
+                    None => {
+                        let found = self.peek().cloned();
+                        return Err(self.error_ahead(format!("Expected a pattern but found {:?}", found)));
                     }
-                    redirect.stdin = Some(Self::parse_redirect(filename)?);
-                    self.next();
                 }
-                Token::OutputRedirect(filename) => {
-                    if redirect.stdout.is_some() {
-                        return Err("Only one output redirection per command is allowed".to_string());
-                    }
-                    redirect.stdout = Some(Self::parse_redirect(filename)?);
-                    self.next();
+                if self.peek() != Some(&Token::Pipe) {
+                    break;
                 }
-                Token::ErrorRedirect(filename) => {
-                    if redirect.stderr.is_some() {
-                        return Err("Only one error redirection per command is allowed".to_string());
-                    }
-                    redirect.stderr = Some(Self::parse_redirect(filename)?);
-                    self.next();
+                self.next();
+            }
+            self.expect(vec![Token::RightParen])?;
+
+            self.skip_newlines();
+            let body = if self.at_terminator() { None } else { Some(self.parse_block(true)?) };
+            let terminator = match self.peek() {
+                Some(Token::DoubleSemicolon) => CaseTerminator::Break,
+                Some(Token::SemicolonAmpersand) => CaseTerminator::FallThrough,
+                Some(Token::DoubleSemicolonAmpersand) => CaseTerminator::Continue,
+                _ => {
+                    self.expect(vec![Token::Esac])?;
+                    arms.push(CaseArm { patterns, body, terminator: CaseTerminator::Break });
+                    break;
                 }
-                _ => break
+            };
+            self.next();
+            arms.push(CaseArm { patterns, body, terminator });
+        }
+        Ok(Expr::Case(word, arms))
+    }
+
+    fn parse_function(&mut self) -> Result<Expr, ParseError> {
+        let name = match self.next() {
+            Some(Token::Command(name) | Token::Argument(name)) => name,
+            Some(token) => return Err(self.error(format!("Expected a function name but found {}", token), Some(token))),
+            None => return Err(self.error_ahead("Expected a function name but found nothing".to_string())),
+        };
+        if self.peek() == Some(&Token::LeftParen) {
+            self.next();
+            self.expect(vec![Token::RightParen])?;
+        }
+        self.parse_function_body(name)
+    }
+
+    fn parse_function_body(&mut self, name: String) -> Result<Expr, ParseError> {
+        self.skip_newlines();
+        match self.peek().cloned() {
+            Some(Token::LeftBrace | Token::LeftParen | Token::If | Token::While | Token::Until | Token::For |
+                 Token::Case) => {
+                let body = self.parse_atom()?;
+                Ok(Expr::Function(name, Box::new(body)))
             }
+            Some(token) => {
+                let message = format!("Expected a compound command as body of {} but found {}", name, token);
+                Err(self.error_ahead(message))
+            }
+            None => Err(self.error_ahead(format!("Expected a compound command as body of {} but found nothing", name))),
         }
-        Ok(Expr::Cmd {
-            name: cmd_type.to_string(),
-            arguments,
-            redirect,
-        })
     }
 
-    fn parse_if(&mut self) -> Result<Expr, String> {
-        let cond = self.parse_expr(0)?;
+    fn next_word(&mut self) -> Option<String> {
+        let word = match self.peek()? {
+            Token::Command(word) |
+            Token::Hyphen(word) |
+            Token::DoubleHyphen(word) |
+            Token::Argument(word) => word.to_string(),
+            Token::EnvVariable(env) => format!("${}", env),
+            _ => return None
+        };
+        self.next();
+        Some(word)
+    }
+
+    fn parse_do_block(&mut self) -> Result<Expr, ParseError> {
+        self.expect(vec![Token::Do])?;
+        let body = self.parse_block(true)?;
+        self.expect(vec![Token::Done])?;
+        Ok(body)
+    }
+
+    fn skip_separator(&mut self) {
+        if self.peek() == Some(&Token::Semicolon) {
+            self.next();
+        }
+        self.skip_newlines();
+    }
+
+    fn parse_compound(&mut self, closing: Token) -> Result<(Expr, Redirect), ParseError> {
+        let body = self.parse_block(true)?;
+        self.expect(vec![closing])?;
+        let mut redirect = Redirect::new(None, None, None);
+        while self.parse_redirects(&mut redirect)? {}
+        Ok((body, redirect))
+    }
+
+    fn parse_redirects(&mut self, redirect: &mut Redirect) -> Result<bool, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::InputRedirect(filename)) => {
+                if redirect.stdin.is_some() {
+                    return Err(self.error_ahead("Only one input redirection per command is allowed".to_string()));
+                }
+                redirect.stdin = Some(self.parse_redirect(filename)?);
+            }
+            Some(Token::OutputRedirect(filename)) => {
+                if redirect.stdout.is_some() {
+                    return Err(self.error_ahead("Only one output redirection per command is allowed".to_string()));
+                }
+                redirect.stdout = Some(self.parse_redirect(filename)?);
+            }
+            Some(Token::ErrorRedirect(filename)) => {
+                if redirect.stderr.is_some() {
+                    return Err(self.error_ahead("Only one error redirection per command is allowed".to_string()));
+                }
+                redirect.stderr = Some(self.parse_redirect(filename)?);
+            }
+            _ => return Ok(false)
+        }
+        self.next();
+        Ok(true)
+    }
+
+    fn parse_if(&mut self) -> Result<Expr, ParseError> {
+        let cond = self.parse_block(true)?;
+        let multiline = self.after_separator;
         self.expect(vec![Token::Then])?;
-        let then_expr = self.parse_expr(0)?;
+        let then_expr = self.parse_block(multiline)?;
+
+        match self.peek() {
+            Some(Token::Elif) => {
+                self.next();
+                let else_expr = self.parse_if()?;
+                return Ok(Expr::IfElse(Box::new(cond), Box::new(then_expr), Box::new(else_expr)));
+            }
+            Some(Token::Fi) => {
+                self.next();
+                return Ok(Expr::If(Box::new(cond), Box::new(then_expr)));
+            }
+            _ => ()
+        }
 
-        if self.expect(vec![Token::Else, Token::DoubleSemicolon, Token::EOL])? == Token::Else {
-            let else_expr = self.parse_expr(0)?;
-            self.expect(vec![Token::DoubleSemicolon, Token::EOL])?;
+        if self.end_if(vec![Token::Else, Token::DoubleSemicolon, Token::EOL])? == Token::Else {
+            let else_expr = self.parse_block(multiline)?;
+            if self.peek() == Some(&Token::Fi) {
+                self.next();
+            } else {
+                self.end_if(vec![Token::DoubleSemicolon, Token::EOL])?;
+            }
             Ok(Expr::IfElse(
                 Box::new(cond),
                 Box::new(then_expr),
@@ -147,19 +446,33 @@ impl<'lexer> Parser<'lexer> {
         }
     }
 
-    fn parse_redirect(filename: &str) -> Result<String, String> {
+    fn end_if(&mut self, should: Vec<Token>) -> Result<Token, ParseError> {
+        match self.peek() {
+            Some(Token::Done | Token::Esac | Token::RightParen | Token::RightBrace) => Ok(Token::EOL),
+            _ => self.expect(should),
+        }
+    }
+
+    fn parse_redirect(&mut self, filename: String) -> Result<String, ParseError> {
         if filename.is_empty() {
-            return Err("Expected a file but found nothing".to_string());
+            return Err(self.error_ahead("Expected a file but found nothing".to_string()));
         }
-        Ok(filename.to_string())
+        Ok(filename)
     }
 
-    fn expect(&mut self, should: Vec<Token>) -> Result<Token, String> {
+    fn expect(&mut self, should: Vec<Token>) -> Result<Token, ParseError> {
         match self.peek() {
             Some(is) if should.iter().any(|should| is == should) =>
                 if is == &Token::EOL { Ok(Token::EOL) } else { Ok(self.next().unwrap()) }
-            Some(is) => Err(format!("Expected one of: {:?} but found: {:?}", should, is)),
-            None => Err("Unexpected end of input".to_string()),
+            Some(is) => {
+                let message = format!("Expected one of: {:?} but found: {:?}", should, is);
+                let error = self.error_ahead(message);
+                Err(ParseError { expected: should, ..error })
+            }
+            None => {
+                let error = self.error_ahead("Unexpected end of input".to_string());
+                Err(ParseError { expected: should, ..error })
+            }
         }
     }
 }
\ No newline at end of file
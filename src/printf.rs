@@ -0,0 +1,355 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+pub fn format(format: &str, arguments: &[String]) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    let mut arguments = arguments.iter().map(String::as_str);
+    loop {
+        let consumed = arguments.len();
+        if format_once(format, &mut arguments, &mut output)? {
+            break;
+        }
+        if arguments.len() == 0 || arguments.len() == consumed {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+pub fn unescape(text: &str) -> (Vec<u8>, bool) {
+    let mut result = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push_char(&mut result, c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => result.push(0x07),
+            Some('b') => result.push(0x08),
+            Some('c') => return (result, true),
+            Some('e' | 'E') => result.push(0x1b),
+            Some('f') => result.push(0x0c),
+            Some('n') => result.push(b'\n'),
+            Some('r') => result.push(b'\r'),
+            Some('t') => result.push(b'\t'),
+            Some('v') => result.push(0x0b),
+            Some('\\') => result.push(b'\\'),
+            Some('0') => result.extend(read_code(&mut chars, 8, 3).map(|code| code as u8)),
+            Some('x') => match read_code(&mut chars, 16, 2) {
+                Some(code) => result.push(code as u8),
+                None => result.extend_from_slice(b"\\x"),
+            },
+            Some('u') => match read_code(&mut chars, 16, 4).and_then(char::from_u32) {
+                Some(c) => push_char(&mut result, c),
+                None => result.extend_from_slice(b"\\u"),
+            },
+            Some(other) => {
+                result.push(b'\\');
+                push_char(&mut result, other);
+            }
+            None => result.push(b'\\'),
+        }
+    }
+    (result, false)
+}
+
+fn push_char(output: &mut Vec<u8>, c: char) {
+    output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+fn read_code(chars: &mut Peekable<Chars>, radix: u32, max: usize) -> Option<u32> {
+    let mut digits = String::new();
+    while digits.len() < max {
+        match chars.peek() {
+            Some(c) if c.is_digit(radix) => digits.push(chars.next().unwrap()),
+            _ => break,
+        }
+    }
+    if digits.is_empty() {
+        return if radix == 8 { Some(0) } else { None };
+    }
+    u32::from_str_radix(&digits, radix).ok()
+}
+
+fn format_once<'a>(
+    format: &str,
+    arguments: &mut impl Iterator<Item = &'a str>,
+    output: &mut Vec<u8>,
+) -> Result<bool, String> {
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut escape = String::from('\\');
+                escape.extend(chars.next());
+                if escape == "\\c" {
+                    return Ok(true);
+                }
+                if escape.chars().nth(1).is_some_and(|c| c.is_digit(8)) {
+                    while escape.len() < 4 && chars.peek().is_some_and(|c| c.is_digit(8)) {
+                        escape.push(chars.next().unwrap());
+                    }
+                    let code = u32::from_str_radix(&escape[1..], 8).unwrap_or(0);
+                    output.push(code as u8);
+                    continue;
+                }
+                if matches!(escape.as_str(), "\\x" | "\\u") {
+                    let max = if escape == "\\x" { 2 } else { 4 };
+                    while escape.len() < max + 2 && chars.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                        escape.push(chars.next().unwrap());
+                    }
+                }
+                output.extend(unescape(&escape).0);
+            }
+            '%' if chars.peek() == Some(&'%') => {
+                chars.next();
+                output.push(b'%');
+            }
+            '%' => {
+                let spec = read_spec(&mut chars, arguments)?;
+                if convert(&spec, arguments.next(), output)? {
+                    return Ok(true);
+                }
+            }
+            c => push_char(output, c),
+        }
+    }
+    Ok(false)
+}
+
+fn read_spec<'a>(chars: &mut Peekable<Chars>, arguments: &mut impl Iterator<Item = &'a str>) -> Result<Spec, String> {
+    let mut spec = Spec {
+        left: false,
+        zero: false,
+        plus: false,
+        space: false,
+        alternate: false,
+        width: None,
+        precision: None,
+        conversion: 's',
+    };
+    while let Some(&c) = chars.peek() {
+        match c {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            ' ' => spec.space = true,
+            '#' => spec.alternate = true,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    spec.width = read_number(chars, arguments)?;
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        spec.precision = Some(read_number(chars, arguments)?.unwrap_or(0));
+    }
+    while chars.peek().is_some_and(|c| matches!(c, 'h' | 'l' | 'L' | 'j' | 'z' | 't')) {
+        chars.next();
+    }
+    spec.conversion = match chars.next() {
+        Some(c) if "diouxXfFeEgGcsbq".contains(c) => c,
+        Some(c) => return Err(format!("{} is not a valid format character", c)),
+        None => return Err("Missing format character".to_string()),
+    };
+    Ok(spec)
+}
+
+fn read_number<'a>(
+    chars: &mut Peekable<Chars>,
+    arguments: &mut impl Iterator<Item = &'a str>,
+) -> Result<Option<usize>, String> {
+    if chars.peek() == Some(&'*') {
+        chars.next();
+        let value = integer(arguments.next().unwrap_or("0"))?;
+        return Ok(Some(value.unsigned_abs() as usize));
+    }
+    let mut digits = String::new();
+    while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+        digits.push(chars.next().unwrap());
+    }
+    Ok(digits.parse::<usize>().ok())
+}
+
+fn integer(argument: &str) -> Result<i64, String> {
+    let trimmed = argument.trim();
+    if let Some(quoted) = trimmed.strip_prefix(['\'', '"']) {
+        return Ok(quoted.chars().next().map_or(0, |c| c as i64));
+    }
+    if trimmed.is_empty() {
+        return Ok(0);
+    }
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let parsed = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None if digits.len() > 1 && digits.starts_with('0') => i64::from_str_radix(&digits[1..], 8),
+        None => digits.parse::<i64>(),
+    };
+    let value = parsed.map_err(|_| format!("{} is not a valid number", argument))?;
+    Ok(if negative { -value } else { value })
+}
+
+fn float(argument: &str) -> Result<f64, String> {
+    let trimmed = argument.trim();
+    if let Some(quoted) = trimmed.strip_prefix(['\'', '"']) {
+        return Ok(quoted.chars().next().map_or(0.0, |c| c as u32 as f64));
+    }
+    if trimmed.is_empty() {
+        return Ok(0.0);
+    }
+    trimmed.parse::<f64>().or_else(|_| integer(trimmed).map(|value| value as f64))
+}
+
+fn convert(spec: &Spec, argument: Option<&str>, output: &mut Vec<u8>) -> Result<bool, String> {
+    let argument = argument.unwrap_or("");
+    let (sign, prefix, body) = match spec.conversion {
+        's' => {
+            let text = match spec.precision {
+                Some(precision) => argument.chars().take(precision).collect(),
+                None => argument.to_string(),
+            };
+            (String::new(), "", text)
+        }
+        'b' => {
+            let (text, stop) = unescape(argument);
+            let fill = " ".repeat(spec.width.unwrap_or(0).saturating_sub(text.len()));
+            let (before, after) = if spec.left { ("", fill.as_str()) } else { (fill.as_str(), "") };
+            output.extend([before.as_bytes(), &text, after.as_bytes()].concat());
+            return Ok(stop);
+        }
+        'q' => (String::new(), "", quote(argument)),
+        'c' => (String::new(), "", argument.chars().next().map(String::from).unwrap_or_default()),
+        'd' | 'i' => {
+            let value = integer(argument)?;
+            let digits = with_precision(value.unsigned_abs().to_string(), spec.precision);
+            (sign(spec, value < 0), "", digits)
+        }
+        'o' | 'u' | 'x' | 'X' => {
+            let value = integer(argument)? as u64;
+            let digits = match spec.conversion {
+                'o' => format!("{:o}", value),
+                'u' => value.to_string(),
+                'x' => format!("{:x}", value),
+                _ => format!("{:X}", value),
+            };
+            let mut digits = with_precision(digits, spec.precision);
+            let prefix = match (spec.alternate && value != 0, spec.conversion) {
+                (true, 'x') => "0x",
+                (true, 'X') => "0X",
+                _ => "",
+            };
+            if spec.alternate && spec.conversion == 'o' && !digits.starts_with('0') {
+                digits.insert(0, '0');
+            }
+            (String::new(), prefix, digits)
+        }
+        _ => {
+            let value = float(argument)?;
+            let precision = spec.precision.unwrap_or(6);
+            let digits = match spec.conversion {
+                _ if value.is_nan() => "nan".to_string(),
+                _ if value.is_infinite() => "inf".to_string(),
+                'f' | 'F' => format!("{:.*}", precision, value.abs()),
+                'e' | 'E' => exponential(value.abs(), precision),
+                _ => general(value.abs(), precision, spec.alternate),
+            };
+            let digits = if spec.conversion.is_uppercase() { digits.to_uppercase() } else { digits };
+            (sign(spec, value.is_sign_negative() && value != 0.0), "", digits)
+        }
+    };
+    let numeric = !matches!(spec.conversion, 's' | 'q' | 'c');
+    let integer_precision = spec.precision.is_some() && "diouxX".contains(spec.conversion);
+    output.extend_from_slice(pad(spec, &sign, prefix, &body, numeric && !integer_precision).as_bytes());
+    Ok(false)
+}
+
+fn sign(spec: &Spec, negative: bool) -> String {
+    match (negative, spec.plus, spec.space) {
+        (true, _, _) => "-",
+        (false, true, _) => "+",
+        (false, false, true) => " ",
+        _ => "",
+    }
+    .to_string()
+}
+
+fn with_precision(digits: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(precision) if digits.len() < precision => "0".repeat(precision - digits.len()) + &digits,
+        _ => digits,
+    }
+}
+
+fn exponential(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let exponent = match value {
+        0.0 => 0,
+        _ => format!("{:.*e}", precision - 1, value)
+            .split_once('e')
+            .and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+            .unwrap_or(0),
+    };
+    let formatted = if exponent < -4 || exponent >= precision as i32 {
+        exponential(value, precision - 1)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+    };
+    if alternate {
+        return formatted;
+    }
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) => format!("{}e{}", trim_zeros(mantissa), exponent),
+        None => trim_zeros(&formatted).to_string(),
+    }
+}
+
+fn trim_zeros(number: &str) -> &str {
+    match number.contains('.') {
+        true => number.trim_end_matches('0').trim_end_matches('.'),
+        false => number,
+    }
+}
+
+pub fn quote(text: &str) -> String {
+    let safe = |c: char| c.is_alphanumeric() || "_-./:=@%+,".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn pad(spec: &Spec, sign: &str, prefix: &str, body: &str, zero_padding: bool) -> String {
+    let len = sign.chars().count() + prefix.len() + body.chars().count();
+    let fill = spec.width.unwrap_or(0).saturating_sub(len);
+    if spec.left {
+        format!("{}{}{}{}", sign, prefix, body, " ".repeat(fill))
+    } else if spec.zero && zero_padding {
+        format!("{}{}{}{}", sign, prefix, "0".repeat(fill), body)
+    } else {
+        format!("{}{}{}{}", " ".repeat(fill), sign, prefix, body)
+    }
+}
@@ -0,0 +1,249 @@
+use std::env;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::expand::read_parameter;
+use crate::utils;
+
+pub const DEFAULT_PS1: &str = "\\w> ";
+pub const DEFAULT_PS2: &str = "> ";
+pub const DEFAULT_PS4: &str = "+ ";
+
+const SHELL_NAME: &str = env!("CARGO_PKG_NAME");
+const SHELL_VERSION: &str = env!("CARGO_PKG_VERSION");
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+pub trait Context {
+    fn status(&self) -> i32;
+    fn working_directory(&self) -> String;
+    fn lookup(&self, name: &str) -> Option<String>;
+    fn substitute(&mut self, command: &str) -> String;
+}
+
+struct Time {
+    hour: u32,
+    minute: u32,
+    second: u32,
+    weekday: usize,
+    month: usize,
+    day: u32,
+}
+
+pub fn default(name: &str) -> &'static str {
+    match name {
+        "PS1" => DEFAULT_PS1,
+        "PS2" => DEFAULT_PS2,
+        "PS4" => DEFAULT_PS4,
+        _ => "",
+    }
+}
+
+pub fn expand(template: &str, context: &mut dyn Context) -> String {
+    let mut prompt = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => expand_escape(&mut chars, context, &mut prompt),
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                let command = read_command(&mut chars);
+                prompt.push_str(&context.substitute(&command));
+            }
+            '$' => match read_parameter(&mut chars) {
+                Some(name) => prompt.push_str(&context.lookup(&name).unwrap_or_default()),
+                None => prompt.push('$'),
+            },
+            c => prompt.push(c),
+        }
+    }
+    prompt
+}
+
+fn expand_escape(chars: &mut Peekable<Chars>, context: &mut dyn Context, prompt: &mut String) {
+    match chars.next() {
+        Some('u') => prompt.push_str(&user()),
+        Some('h') => prompt.push_str(hostname().split('.').next().unwrap_or_default()),
+        Some('H') => prompt.push_str(&hostname()),
+        Some('w') => prompt.push_str(&abbreviate_home(&context.working_directory(), context)),
+        Some('W') => {
+            let directory = abbreviate_home(&context.working_directory(), context);
+            let name = Path::new(&directory).file_name().map(|name| name.to_string_lossy().to_string());
+            prompt.push_str(&name.unwrap_or(directory));
+        }
+        Some('$') => prompt.push(if is_root() { '#' } else { '$' }),
+        Some('?') => prompt.push_str(&context.status().to_string()),
+        Some('j') => prompt.push('0'),
+        Some('s') => prompt.push_str(SHELL_NAME),
+        Some('v' | 'V') => prompt.push_str(SHELL_VERSION),
+        Some('t') => {
+            let time = local_time();
+            prompt.push_str(&format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second));
+        }
+        Some('T') => {
+            let time = local_time();
+            prompt.push_str(&format!("{:02}:{:02}:{:02}", twelve_hour(time.hour), time.minute, time.second));
+        }
+        Some('@') => {
+            let time = local_time();
+            let period = if time.hour < 12 { "AM" } else { "PM" };
+            prompt.push_str(&format!("{:02}:{:02} {}", twelve_hour(time.hour), time.minute, period));
+        }
+        Some('A') => {
+            let time = local_time();
+            prompt.push_str(&format!("{:02}:{:02}", time.hour, time.minute));
+        }
+        Some('d') => {
+            let time = local_time();
+            prompt.push_str(&format!("{} {} {:02}", WEEKDAYS[time.weekday], MONTHS[time.month], time.day));
+        }
+        Some('n') => prompt.push('\n'),
+        Some('r') => prompt.push('\r'),
+        Some('a') => prompt.push('\x07'),
+        Some('e') => prompt.push('\x1b'),
+        Some('[' | ']') => (),
+        Some('\\') => prompt.push('\\'),
+        Some(digit @ '0'..='7') => {
+            let mut code = digit.to_digit(8).unwrap_or(0);
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => code = code * 8 + digit,
+                    None => break,
+                }
+                chars.next();
+            }
+            prompt.extend(char::from_u32(code));
+        }
+        Some(other) => {
+            prompt.push('\\');
+            prompt.push(other);
+        }
+        None => prompt.push('\\'),
+    }
+}
+
+fn read_command(chars: &mut Peekable<Chars>) -> String {
+    let mut command = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                command.push(c);
+                for quoted in chars.by_ref() {
+                    command.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        command.push(c);
+    }
+    command
+}
+
+fn abbreviate_home(directory: &str, context: &dyn Context) -> String {
+    match context.lookup("HOME").or_else(|| context.lookup("USERPROFILE")) {
+        Some(home) => utils::abbreviate_home(directory, &home),
+        None => directory.to_string(),
+    }
+}
+
+fn twelve_hour(hour: u32) -> u32 {
+    match hour % 12 {
+        0 => 12,
+        hour => hour,
+    }
+}
+
+fn user() -> String {
+    env::var("USER").or_else(|_| env::var("USERNAME")).or_else(|_| env::var("LOGNAME")).unwrap_or_else(|_| login_name())
+}
+
+#[cfg(unix)]
+fn login_name() -> String {
+    let passwd = unsafe { libc::getpwuid(libc::geteuid()) };
+    if passwd.is_null() {
+        return String::new();
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr((*passwd).pw_name) };
+    name.to_string_lossy().to_string()
+}
+
+#[cfg(not(unix))]
+fn login_name() -> String {
+    String::new()
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+        return String::new();
+    }
+    let length = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..length]).to_string()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn local_time() -> Time {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    Time {
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+        weekday: tm.tm_wday as usize % 7,
+        month: tm.tm_mon as usize % 12,
+        day: tm.tm_mday as u32,
+    }
+}
+
+#[cfg(not(unix))]
+fn local_time() -> Time {
+    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    let days = seconds / 86400;
+    let (month, day) = month_and_day(days as i64);
+    Time {
+        hour: (seconds % 86400 / 3600) as u32,
+        minute: (seconds % 3600 / 60) as u32,
+        second: (seconds % 60) as u32,
+        weekday: ((days + 4) % 7) as usize,
+        month,
+        day,
+    }
+}
+
+#[cfg(not(unix))]
+fn month_and_day(days: i64) -> (usize, u32) {
+    let days = days + 719468;
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 2 } else { shifted_month - 10 };
+    (month as usize, day)
+}
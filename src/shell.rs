@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::arithmetic::Variables;
+use crate::builtins::Builtin;
+use crate::environment::Environment;
+use crate::error::ShellError;
+use crate::interpreter::Interpreter;
+use crate::utils::{get_program_dir, join_program_dirs};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecResult {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        self.status == 0
+    }
+
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    pub fn stderr_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+struct Callback<F: FnMut(&[u8])>(F);
+
+impl<F: FnMut(&[u8])> Write for Callback<F> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        (self.0)(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct ShellBuilder {
+    cwd: Option<PathBuf>,
+    programs_dirs: Vec<PathBuf>,
+    environment: HashMap<String, String>,
+    variables: Vec<(String, String)>,
+    builtins: Vec<Box<dyn Builtin>>,
+    stdout: Option<Box<dyn Write>>,
+    stderr: Option<Box<dyn Write>>,
+}
+
+impl ShellBuilder {
+    pub fn cwd(mut self, cwd: impl AsRef<Path>) -> ShellBuilder {
+        self.cwd = Some(cwd.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn programs_dir(mut self, programs_dir: impl AsRef<Path>) -> ShellBuilder {
+        self.programs_dirs.push(programs_dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn env(mut self, name: &str, value: &str) -> ShellBuilder {
+        self.environment.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn clear_env(mut self) -> ShellBuilder {
+        self.environment.clear();
+        self
+    }
+
+    pub fn var(mut self, name: &str, value: &str) -> ShellBuilder {
+        self.variables.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn builtin(mut self, builtin: impl Builtin + 'static) -> ShellBuilder {
+        self.builtins.push(Box::new(builtin));
+        self
+    }
+
+    pub fn stdout(mut self, writer: impl Write + 'static) -> ShellBuilder {
+        self.stdout = Some(Box::new(writer));
+        self
+    }
+
+    pub fn stderr(mut self, writer: impl Write + 'static) -> ShellBuilder {
+        self.stderr = Some(Box::new(writer));
+        self
+    }
+
+    pub fn on_stdout(self, callback: impl FnMut(&[u8]) + 'static) -> ShellBuilder {
+        self.stdout(Callback(callback))
+    }
+
+    pub fn on_stderr(self, callback: impl FnMut(&[u8]) + 'static) -> ShellBuilder {
+        self.stderr(Callback(callback))
+    }
+
+    pub fn build(self) -> Shell {
+        let cwd = match self.cwd {
+            Some(cwd) => env::current_dir().unwrap_or_default().join(cwd),
+            None => env::current_dir().unwrap_or_default(),
+        };
+        let programs_dir = match self.programs_dirs.is_empty() {
+            true => get_program_dir(),
+            false => join_program_dirs(&self.programs_dirs.iter().map(|directory| cwd.join(directory)).collect::<Vec<PathBuf>>()),
+        };
+        let environment = Environment::isolated(cwd, self.environment);
+        let mut interpreter = Interpreter::new(&programs_dir).with_environment(environment).with_writers(self.stdout, self.stderr);
+        for (name, value) in self.variables {
+            Variables::set(&mut interpreter, &name, value);
+        }
+        for builtin in self.builtins {
+            interpreter.register(builtin);
+        }
+        Shell { interpreter }
+    }
+}
+
+pub struct Shell {
+    interpreter: Interpreter,
+}
+
+impl Shell {
+    pub fn builder() -> ShellBuilder {
+        ShellBuilder {
+            cwd: None,
+            programs_dirs: vec![],
+            environment: env::vars_os()
+                .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+                .collect(),
+            variables: vec![],
+            builtins: vec![],
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    pub fn new() -> Shell {
+        Shell::builder().build()
+    }
+
+    pub fn run(&mut self, input: &str) -> ExecResult {
+        self.interpreter.run(input)
+    }
+
+    pub fn cwd(&self) -> PathBuf {
+        self.interpreter.environment().current_dir()
+    }
+
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.interpreter.get(name)
+    }
+
+    pub fn register(&mut self, builtin: impl Builtin + 'static) {
+        self.interpreter.register(builtin);
+    }
+
+    pub fn errors(&self) -> &[ShellError] {
+        self.interpreter.errors()
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Shell {
+        Shell::new()
+    }
+}
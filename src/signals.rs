@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const EXIT: &str = "EXIT";
+pub const ERR: &str = "ERR";
+
+const MAX_SIGNAL: usize = 64;
+
+#[cfg(unix)]
+const SIGNALS: [(&str, i32); 7] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+];
+
+#[cfg(not(unix))]
+const SIGNALS: [(&str, i32); 2] = [
+    ("INT", 2),
+    ("TERM", 15),
+];
+
+static PENDING: [AtomicBool; MAX_SIGNAL + 1] = [const { AtomicBool::new(false) }; MAX_SIGNAL + 1];
+
+pub fn names() -> Vec<&'static str> {
+    let mut names = vec![EXIT];
+    names.extend(SIGNALS.iter().map(|(name, _)| *name));
+    names.push(ERR);
+    names
+}
+
+pub fn parse_spec(spec: &str) -> Option<&'static str> {
+    if let Ok(number) = spec.parse::<i32>() {
+        if number == 0 {
+            return Some(EXIT);
+        }
+        return SIGNALS.iter().find(|(_, n)| *n == number).map(|(name, _)| *name);
+    }
+    let upper = spec.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    names().into_iter().find(|n| *n == name)
+}
+
+pub fn number(name: &str) -> Option<i32> {
+    SIGNALS.iter().find(|(n, _)| *n == name).map(|(_, number)| *number)
+}
+
+pub fn name(number: i32) -> Option<&'static str> {
+    SIGNALS.iter().find(|(_, n)| *n == number).map(|(name, _)| *name)
+}
+
+pub fn take_pending() -> Vec<i32> {
+    (1..=MAX_SIGNAL)
+        .filter(|&number| PENDING[number].swap(false, Ordering::SeqCst))
+        .map(|number| number as i32)
+        .collect()
+}
+
+#[cfg(unix)]
+extern "C" fn record(signal: libc::c_int) {
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn install(number: i32, handler: libc::sighandler_t) {
+    unsafe {
+        libc::signal(number, handler);
+    }
+}
+
+#[cfg(unix)]
+pub fn catch(number: i32) {
+    install(number, record as extern "C" fn(libc::c_int) as libc::sighandler_t)
+}
+
+#[cfg(unix)]
+pub fn ignore(number: i32) {
+    install(number, libc::SIG_IGN)
+}
+
+#[cfg(unix)]
+pub fn reset(number: i32) {
+    install(number, libc::SIG_DFL)
+}
+
+#[cfg(not(unix))]
+pub fn catch(_number: i32) {}
+
+#[cfg(not(unix))]
+pub fn ignore(_number: i32) {}
+
+#[cfg(not(unix))]
+pub fn reset(_number: i32) {}
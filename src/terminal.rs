@@ -0,0 +1,76 @@
+use std::env;
+use std::io;
+use std::io::{stdin, stdout, IsTerminal, Write};
+
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+const CLEAR_SCROLLBACK: &str = "\x1b[3J";
+const RESET: &str = "\x1bc\x1b[!p\x1b[?1049l\x1b[?25h\x1b[0m";
+
+pub fn supports_ansi() -> bool {
+    let term = env::var("TERM").ok().filter(|term| !term.is_empty());
+    stdout().is_terminal() && term.map_or(cfg!(windows), |term| term != "dumb")
+}
+
+pub fn clear(scrollback: bool) -> io::Result<()> {
+    if !supports_ansi() {
+        return Ok(());
+    }
+    let mut stdout = stdout();
+    stdout.write_all(CLEAR_SCREEN.as_bytes())?;
+    if scrollback {
+        stdout.write_all(CLEAR_SCROLLBACK.as_bytes())?;
+    }
+    stdout.flush()
+}
+
+pub fn reset() -> io::Result<()> {
+    if !supports_ansi() {
+        return Ok(());
+    }
+    let mut stdout = stdout();
+    stdout.write_all(RESET.as_bytes())?;
+    stdout.write_all(CLEAR_SCREEN.as_bytes())?;
+    stdout.flush()
+}
+
+pub fn restore_modes() -> io::Result<()> {
+    match stdin().is_terminal() {
+        true => restore_sane_modes(),
+        false => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn restore_sane_modes() -> io::Result<()> {
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    termios.c_iflag |= libc::BRKINT | libc::ICRNL | libc::IXON;
+    termios.c_iflag &= !(libc::IGNBRK | libc::INLCR | libc::IGNCR | libc::IXOFF);
+    termios.c_oflag |= libc::OPOST | libc::ONLCR;
+    termios.c_oflag &= !(libc::OCRNL | libc::ONOCR | libc::ONLRET);
+    termios.c_lflag |= libc::ISIG | libc::ICANON | libc::IEXTEN | libc::ECHO | libc::ECHOE | libc::ECHOK;
+    termios.c_lflag &= !(libc::ECHONL | libc::NOFLSH | libc::TOSTOP);
+    for (index, value) in [
+        (libc::VINTR, 0x03),
+        (libc::VQUIT, 0x1c),
+        (libc::VERASE, 0x7f),
+        (libc::VKILL, 0x15),
+        (libc::VEOF, 0x04),
+        (libc::VSUSP, 0x1a),
+    ] {
+        termios.c_cc[index] = value;
+    }
+
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_sane_modes() -> io::Result<()> {
+    Ok(())
+}
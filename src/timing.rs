@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+pub const DEFAULT_FORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+pub const POSIX_FORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+pub struct Times {
+    pub real: Duration,
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+#[cfg(unix)]
+pub fn cpu_times() -> (Duration, Duration) {
+    let (self_user, self_sys) = rusage(libc::RUSAGE_SELF);
+    let (children_user, children_sys) = rusage(libc::RUSAGE_CHILDREN);
+    (self_user + children_user, self_sys + children_sys)
+}
+
+#[cfg(unix)]
+fn rusage(who: libc::c_int) -> (Duration, Duration) {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+        return (Duration::ZERO, Duration::ZERO);
+    }
+    (to_duration(usage.ru_utime), to_duration(usage.ru_stime))
+}
+
+#[cfg(unix)]
+fn to_duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+#[cfg(not(unix))]
+pub fn cpu_times() -> (Duration, Duration) {
+    (Duration::ZERO, Duration::ZERO)
+}
+
+pub fn format(format: &str, times: &Times) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        let precision = match chars.peek().and_then(|c| c.to_digit(10)) {
+            Some(digit) => {
+                chars.next();
+                digit.min(3) as usize
+            }
+            None => 3,
+        };
+        let long = chars.peek() == Some(&'l');
+        if long {
+            chars.next();
+        }
+
+        let seconds = match chars.next() {
+            Some('R') => times.real.as_secs_f64(),
+            Some('U') => times.user.as_secs_f64(),
+            Some('S') => times.sys.as_secs_f64(),
+            Some('P') => {
+                let real = times.real.as_secs_f64();
+                let cpu = times.user.as_secs_f64() + times.sys.as_secs_f64();
+                let percent = if real > 0.0 { cpu / real * 100.0 } else { 0.0 };
+                result.push_str(&format!("{:.*}", precision.min(2), percent));
+                continue;
+            }
+            Some('%') => {
+                result.push('%');
+                continue;
+            }
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+                continue;
+            }
+            None => {
+                result.push('%');
+                break;
+            }
+        };
+
+        if long {
+            let minutes = (seconds / 60.0).floor();
+            result.push_str(&format!("{}m{:.*}s", minutes, precision, seconds - minutes * 60.0));
+        } else {
+            result.push_str(&format!("{:.*}", precision, seconds));
+        }
+    }
+    result
+}
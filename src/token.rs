@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Ampersand,
     DoubleAmpersand,
@@ -8,11 +8,31 @@ pub enum Token {
     DoublePipe,
     Semicolon,
     DoubleSemicolon,
+    SemicolonAmpersand,
+    DoubleSemicolonAmpersand,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
     If,
     Then,
+    Elif,
     Else,
+    Fi,
+    While,
+    Until,
+    For,
+    Do,
+    Done,
+    Case,
+    Esac,
+    Function,
+    Bang,
+    Time,
     EOL,
 
+    Arithmetic(String),
+    Conditional(String),
     InputRedirect(String),
     OutputRedirect(String),
     ErrorRedirect(String),
@@ -27,4 +47,17 @@ impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
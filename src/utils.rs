@@ -1,27 +1,55 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::{env, io};
+use std::env::consts::EXE_SUFFIX;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use crate::ast::Expr;
-use crate::config::FOLDER_NAME;
+use crate::config::{FOLDER_NAME, PROGRAMS_VARIABLE};
 use crate::lexer::Lexer;
+use crate::error::{ParseError, ShellError};
 use crate::parser::Parser;
 
 pub fn get_program_dir() -> String {
-    let prefix = env::current_dir().unwrap().display().to_string();
-    prefix + FOLDER_NAME
+    if let Some(directories) = env::var_os(PROGRAMS_VARIABLE).filter(|directories| !directories.is_empty()) {
+        return directories.to_string_lossy().into_owned();
+    }
+    let beside_exe = env::current_exe().ok().and_then(|exe| Some(exe.parent()?.join(FOLDER_NAME)));
+    join_program_dirs(&beside_exe.into_iter().collect::<Vec<PathBuf>>())
+}
+
+pub fn join_program_dirs(directories: &[PathBuf]) -> String {
+    env::join_paths(directories).map(|paths| paths.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+pub fn find_program(program_dirs: &str, name: &str) -> Option<PathBuf> {
+    let executable = format!("{}{}", name, EXE_SUFFIX);
+    env::split_paths(program_dirs)
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .flat_map(|directory| [directory.join(name), directory.join(&executable)])
+        .find(|path| path.is_file())
 }
 
-pub fn parse(input: &str, program_dir: &str) -> Result<Expr, String> {
-    let lexer = Lexer::new(&input, program_dir);
+pub fn parse(input: &str, program_dir: &str) -> Result<Expr, ParseError> {
+    parse_with_aliases(input, program_dir, &HashMap::new())
+}
+
+pub fn parse_with_aliases(input: &str, program_dir: &str, aliases: &HashMap<String, String>) -> Result<Expr, ParseError> {
+    let lexer = Lexer::new(input, program_dir).with_aliases(aliases);
     let mut parser = Parser::new(lexer);
     parser.parse()
 }
 
+pub fn is_incomplete(input: &str, program_dir: &str, aliases: &HashMap<String, String>) -> bool {
+    let lexer = Lexer::new(input, program_dir).with_aliases(aliases);
+    let mut parser = Parser::new(lexer);
+    parser.parse().is_err() && parser.at_end()
+}
+
 pub fn read_stdin() -> String {
     let mut result = String::new();
     let lines = io::stdin().lines();
-    lines.for_each(|l| result.push_str(&*format!("{}\r\n", l.unwrap())));
+    lines.for_each(|l| result.push_str(&format!("{}\r\n", l.unwrap())));
     result[0..result.len() - 1].to_string()
 }
 
@@ -33,28 +61,56 @@ pub fn is_dir(path: &str) -> bool {
     Path::new(path).is_dir()
 }
 
-pub fn read_file(path: &str) -> Result<String, String> {
+pub fn home_dir() -> Option<String> {
+    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
+}
+
+pub fn abbreviate_home(path: &str, home: &str) -> String {
+    if home.trim_end_matches('/').is_empty() {
+        return path.to_string();
+    }
+    match Path::new(path).strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => path.to_string(),
+    }
+}
+
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+pub fn read_file(path: &str) -> Result<String, ShellError> {
     match File::open("./".to_string() + path) {
         Ok(mut file) => {
             let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok() {
-                Ok(contents)
-            } else {
-                Err(format!("Can't read file: {}", path))
+            match file.read_to_string(&mut contents) {
+                Ok(_) => Ok(contents),
+                Err(error) => Err(ShellError::Read { path: path.to_string(), kind: error.kind() }),
             }
         }
-        Err(_) => Err(format!("Can't open file: {}", path))
+        Err(error) => Err(ShellError::Open { path: path.to_string(), kind: error.kind() })
     }
 }
 
 pub fn read_files(files: Vec<String>, stdout: &mut String, stderr: &mut String) {
     for file in &files {
-        match read_file(&file) {
+        match read_file(file) {
             Ok(result) => {
                 stdout.push_str(&(result + "\r\n"));
             }
             Err(error) => {
-                stderr.push_str(&(error + "\r\n"));
+                stderr.push_str(&format!("{}\r\n", error));
             }
         }
     }
@@ -80,11 +136,54 @@ pub fn process_options(options: Vec<String>, pref: Vec<char>) -> Result<Vec<char
     Ok(valid_result)
 }
 
+pub struct OptionSpec {
+    pub short: char,
+    pub help: &'static str,
+}
+
+pub struct ProgramSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub about: &'static str,
+    pub options: &'static [OptionSpec],
+}
+
+impl ProgramSpec {
+    pub fn short_options(&self) -> Vec<char> {
+        self.options.iter().map(|option| option.short).collect()
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!("Usage: {} {}\n{}\n\nOptions:\n", self.name, self.usage, self.about);
+        let options = self.options.iter().map(|option| (format!("-{}", option.short), option.help));
+        let standard = [("--help".to_string(), "Display this help and exit"), ("--version".to_string(), "Display version information and exit")];
+        for (flag, description) in options.chain(standard) {
+            help.push_str(&format!("  {:<12}{}\n", flag, description));
+        }
+        help
+    }
+
+    pub fn version(&self) -> String {
+        format!("{} (shell) {}\n", self.name, env!("CARGO_PKG_VERSION"))
+    }
+
+    pub fn standard_option(&self, arguments: &[String]) -> Option<String> {
+        arguments
+            .iter()
+            .take_while(|argument| *argument != "--")
+            .find_map(|argument| match argument.as_str() {
+                "--help" => Some(self.help()),
+                "--version" => Some(self.version()),
+                _ => None,
+            })
+    }
+}
+
 pub fn get_args() -> Vec<String> {
     env::args().skip(1).collect::<Vec<String>>()
 }
 
-pub fn eq(arguments: &Vec<String>) -> bool {
+pub fn eq(arguments: &[String]) -> bool {
     let first_arg = arguments.first().unwrap();
     arguments
         .iter()
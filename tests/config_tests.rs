@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use shell::config::Config;
+use shell::error::ConfigError;
+use shell::interpreter::Interpreter;
+use shell::utils::{get_program_dir, parse_with_aliases};
+
+#[test]
+fn config_parse_test() {
+    let source = r#"
+# shell settings
+programs = ["/opt/shell/programs", '/usr/local/shell'] # searched in order
+prompt = "\\w \"$\" "
+
+[history]
+size = 2_000
+path = "/tmp/history"
+
+[options]
+pipefail = true
+ignoreeof = false
+
+[aliases]
+ll = "ls -l # not a comment"
+"g s" = 'git status'
+"#;
+    let config = Config::parse(source).unwrap();
+    assert_eq!(config.programs, vec![PathBuf::from("/opt/shell/programs"), PathBuf::from("/usr/local/shell")]);
+    assert_eq!(config.prompt, Some("\\w \"$\" ".to_string()));
+    assert_eq!((config.history_size, config.history_path), (2000, Some(PathBuf::from("/tmp/history"))));
+    assert_eq!(config.options, vec![("pipefail".to_string(), true), ("ignoreeof".to_string(), false)]);
+    assert_eq!(config.aliases, vec![
+        ("ll".to_string(), "ls -l # not a comment".to_string()),
+        ("g s".to_string(), "git status".to_string()),
+    ]);
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    assert_eq!(Config::default().history_path, None);
+}
+
+#[test]
+fn config_errors_test() {
+    let error = |source: &str| Config::parse(source).unwrap_err();
+
+    assert_eq!(error("prompt = '> '\nprompt"), ConfigError {
+        path: None,
+        line: 2,
+        message: "Expected key = value but found prompt".to_string(),
+    });
+    assert_eq!(error("[colors]").message, "Unknown section [colors]");
+    assert_eq!(error("colors = true").message, "Unknown key colors");
+    assert_eq!(error("[history]\nsize = \"big\"").message, "Invalid value for size");
+    assert_eq!(error("prompt = \"> ").message, "Expected \" at the end of the string");
+    assert_eq!(error("programs = ['a' 'b']").message, "Expected , or ] in the array");
+    assert_eq!(error("\nprompt = \"a\" b").to_string(), "line 2: Unexpected b after the value");
+}
+
+#[test]
+fn config_overrides_test() {
+    let environment = HashMap::from([
+        ("SHELL_PROGRAMS", "/first:/second"),
+        ("SHELL_HISTSIZE", "5"),
+        ("SHELL_HISTFILE", ""),
+        ("SHELL_PROMPT", "$ "),
+        ("SHELL_OPTIONS", "ignoreeof, pipefail=false"),
+    ]);
+    let lookup = |name: &str| environment.get(name).map(|value| value.to_string());
+    let config = Config::parse("prompt = '> '\n[options]\npipefail = true").unwrap().with_overrides(lookup).unwrap();
+
+    assert_eq!(config.programs, vec![PathBuf::from("/first"), PathBuf::from("/second")]);
+    assert_eq!((config.history_size, config.history_path), (5, None));
+    assert_eq!(config.prompt, Some("$ ".to_string()));
+    assert_eq!(config.options, vec![("ignoreeof".to_string(), true), ("pipefail".to_string(), false)]);
+
+    let error = Config::default().with_overrides(|name| (name == "SHELL_HISTSIZE").then(|| "lots".to_string()));
+    assert_eq!(error.unwrap_err().to_string(), "SHELL_HISTSIZE: Expected a history size but found lots");
+}
+
+#[test]
+fn config_interpreter_test() {
+    let config = Config::parse("prompt = 'config> '\n[options]\npipefail = true\n[aliases]\nhi = 'echo hello'").unwrap();
+    let mut interpreter = Interpreter::new(&get_program_dir()).with_capture().with_config(&config);
+
+    assert!(interpreter.option("pipefail"));
+    assert_eq!(interpreter.prompt("PS1"), "config> ");
+    let ast = parse_with_aliases("hi\n", &get_program_dir(), interpreter.aliases()).unwrap();
+    assert_eq!(interpreter.eval(&ast), (vec![], vec!["hello".to_string()]));
+
+    let config = Config::parse("[options]\nmissing = true").unwrap();
+    let interpreter = Interpreter::new(&get_program_dir()).with_capture().with_config(&config);
+    assert!(!interpreter.option("missing"));
+    assert_eq!(interpreter.errors().len(), 1);
+}
@@ -2,7 +2,7 @@
 
 use shell::ast::Expr;
 use shell::interpreter::Interpreter;
-use shell::utils::{get_program_dir, parse};
+use shell::utils::{abbreviate_home, get_program_dir, parse};
 
 fn eval(input: &str) -> (Vec<String>, Vec<String>) {
     let program_dir = get_program_dir();
@@ -10,7 +10,7 @@ fn eval(input: &str) -> (Vec<String>, Vec<String>) {
         Ok(ast) => ast,
         Err(e) => panic!("{}", e)
     };
-    let mut interpreter = Interpreter::new(&program_dir);
+    let mut interpreter = Interpreter::new(&program_dir).with_capture();
     interpreter.eval(&ast)
 }
 
@@ -30,6 +30,9 @@ fn interpret_if_test() {
     assert_output("if false then echo something", vec![]);
     assert_output("if true then echo true else echo false", vec!["true".to_string()]);
     assert_output("if false then echo true else echo false", vec!["false".to_string()]);
+    assert_output("echo else", vec!["else".to_string()]);
+    assert_output("if true; then echo then; fi", vec!["then".to_string()]);
+    assert_output("if false; then :; else echo else; fi", vec!["else".to_string()]);
 }
 
 #[test]
@@ -61,3 +64,389 @@ fn interpret_logic_test() {
     assert_output("if true && false || true && true then echo true else echo false", vec!["true".to_string()]);
     // todo: test all combinations
 }
+
+#[test]
+fn interpret_trap_test() {
+    assert_output("trap 'cd .' int term ; trap -p", vec!["trap -- 'cd .' INT\ntrap -- 'cd .' TERM".to_string()]);
+    assert_output("trap 'cd .' SIGINT ; trap - int ; trap -p", vec![]);
+    assert_output("trap 'trap -p err' err ; cd ./does_not_exist", vec!["trap -- 'trap -p err' ERR".to_string()]);
+    assert_output("trap 'trap -p err' err ; if cd ./does_not_exist then cd .", vec![]);
+    assert_error("trap 'cd .' nosignal", vec!["nosignal is not a valid signal specification".to_string()]);
+}
+
+#[test]
+fn interpret_groups_test() {
+    eval("(export subshell_variable=1)");
+    assert!(std::env::var("subshell_variable").is_err());
+    eval("{ export group_variable=1 ; }");
+    assert_eq!(std::env::var("group_variable"), Ok("1".to_string()));
+
+    assert_output("trap 'cd .' int ; (trap 'cd ..' int ; exit ; trap -p) ; trap -p",
+                  vec!["trap -- 'cd .' INT".to_string()]);
+
+    let path = std::env::temp_dir().join("shell_group_output.txt");
+    let input = format!("trap 'cd .' term ; {{ trap -p ; cd ./does_not_exist ; }} > {} 2> /dev/null", path.display());
+    assert_output(&input, vec![]);
+    assert_error(&input, vec![]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "trap -- 'cd .' TERM\n");
+}
+
+#[test]
+fn interpret_loops_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export for_result= ; for x in a 'b c' d; do export for_result=$for_result[$x]; done");
+    assert_eq!(var("for_result"), "[a][b c][d]");
+
+    eval("export c_for_result= ; for ((i = 0; i < 10; i += 2)); do export c_for_result=$c_for_result$i; done");
+    assert_eq!(var("c_for_result"), "02468");
+
+    eval("export while_dir=. ; export while_result= ; while cd $while_dir ; do export while_dir=./does_not_exist ; export while_result=$while_result.; done");
+    assert_eq!(var("while_result"), ".");
+
+    eval("export until_result= ; until cd ./does_not_exist 2> /dev/null ; do export until_result=finished ; break ; done");
+    assert_eq!(var("until_result"), "finished");
+
+    eval("export break_result= ; for x in 1 2; do for y in a b; do export break_result=$break_result$x$y; continue 2; done; done");
+    assert_eq!(var("break_result"), "1a2a");
+
+    eval("export nested_result= ; for x in 1 2; do for y in a b; do export nested_result=$nested_result$x$y; break 2; done; done");
+    assert_eq!(var("nested_result"), "1a");
+
+    eval("export implicit_for_result= ; f() { for x; do export implicit_for_result=$implicit_for_result[$x]; done; } ; f a 'b c'");
+    assert_eq!(var("implicit_for_result"), "[a][b c]");
+
+    assert_output("i=0 ; while (( i < 3 )); do (( i += 1 )); done ; echo $i ; (( i - 3 )) || echo zero ; (( 1 < 2 )) && echo yes",
+                  vec!["3".to_string(), "zero".to_string(), "yes".to_string()]);
+
+    assert_error("break", vec!["Break and continue are only meaningful in a loop".to_string()]);
+}
+
+#[test]
+fn interpret_elif_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export elif_result= ; for dir in . ./does_not_exist ./missing; do \
+          if cd ./missing 2> /dev/null; then export elif_result=$elif_result[missing] ; \
+          elif cd $dir 2> /dev/null; then export elif_result=$elif_result[found:$dir] \
+          else export elif_result=$elif_result[none] ; \
+          fi; done");
+    assert_eq!(var("elif_result"), "[found:.][none][none]");
+
+    eval("export multiline_result=\nif cd ./does_not_exist 2> /dev/null\nthen\n  export multiline_result=wrong\nelse\n  export multiline_result=right\nfi");
+    assert_eq!(var("multiline_result"), "right");
+}
+
+#[test]
+fn interpret_case_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export case_result= ; for x in apple Banana 'c d' x.rs; do case $x in \
+          a*|b*) export case_result=$case_result[ab:$x] ;; \
+          [A-Z]*) export case_result=$case_result[upper:$x] ;& \
+          'c d') export case_result=$case_result[fell:$x] ;; \
+          *.rs) export case_result=$case_result[rs] ;;& \
+          *) export case_result=$case_result[any:$x] ; esac; done");
+    assert_eq!(var("case_result"), "[ab:apple][upper:Banana][fell:Banana][fell:c d][rs][any:x.rs]");
+
+    eval("export quoted_case_result= ; case '*' in '*') export quoted_case_result=star ;; esac ; \
+          case x in '*') export quoted_case_result=wrong ;; esac");
+    assert_eq!(var("quoted_case_result"), "star");
+
+    eval("export glob_result= ; for f in ./tests/files/*.txt; do export glob_result=$glob_result$f; done");
+    assert_eq!(var("glob_result"), "./tests/files/tmp.txt");
+}
+
+#[test]
+fn interpret_functions_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export function_result= ; record() { export function_result=$function_result[$1:$#]; } ; record a b ; record c");
+    assert_eq!(var("function_result"), "[a:2][c:1]");
+
+    eval("export local_result=outer ; f() { local local_result=inner ; export local_seen=$local_result ; g ; } ; \
+          g() { export local_nested=$local_result ; } ; f");
+    assert_eq!(var("local_result"), "outer");
+    assert_eq!(var("local_seen"), "inner");
+    assert_eq!(var("local_nested"), "inner");
+
+    eval("export return_result= ; f() { return 1 ; export return_result=wrong ; } ; f || export return_result=right");
+    assert_eq!(var("return_result"), "right");
+
+    eval("export recursion_result= ; count() { case $1 in xxx) return ;; esac ; \
+          export recursion_result=$recursion_result$1 ; count x$1 ; } ; count x");
+    assert_eq!(var("recursion_result"), "xxx");
+
+    assert_error("loop() { loop ; } ; loop", vec!["loop: maximum function nesting level of 256 exceeded".to_string()]);
+    assert_error("return", vec!["Return is only meaningful in a function or sourced file".to_string()]);
+    assert_error("local x=1", vec!["Local is only meaningful in a function".to_string()]);
+}
+
+#[test]
+fn interpret_negation_and_time_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export negation_result= ; ! cd ./does_not_exist 2> /dev/null && export negation_result=right");
+    assert_eq!(var("negation_result"), "right");
+
+    eval("export negated_if_result= ; if ! cd . ; then export negated_if_result=wrong ; else export negated_if_result=right ; fi");
+    assert_eq!(var("negated_if_result"), "right");
+
+    assert_error("TIMEFORMAT='took %0R%% [%x]' ; time cd .", vec!["took 0% [%x]".to_string()]);
+    assert_error("TIMEFORMAT='' ; time cd .", vec![]);
+}
+
+#[test]
+fn interpret_exit_status_test() {
+    let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+
+    assert_output("f() { return 3 ; } ; f ; echo $?", lines(&["3"]));
+    assert_output("(exit 42) ; echo $? ; (exit 4) || echo $?", lines(&["42", "4"]));
+    assert_output("f() { (exit 5) ; return ; } ; f ; echo $? ; ! f ; echo $?", lines(&["5", "0"]));
+    assert_output("(exit 7) ; (exit) ; echo $? ; true && (exit 8) ; echo $?", lines(&["7", "8"]));
+    assert_output("set -o pipefail ; (exit 6) | (exit 9) | true ; echo $?", lines(&["9"]));
+    assert_output("f() { builtin return 2 ; } ; f ; echo $? ; g() { command return 3 ; } ; g ; echo $?", lines(&["2", "3"]));
+}
+
+#[test]
+fn interpret_conditionals_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+    eval("export test_result= ; \
+          test -d ./tests && export test_result=${test_result}d ; \
+          [ -f ./tests/files/tmp.txt -a ! -f ./tests ] && export test_result=${test_result}f ; \
+          [ 3 -lt 10 -o 1 -gt 2 ] && export test_result=${test_result}i ; \
+          [ -z '' ] && [ -n x ] && export test_result=${test_result}s ; \
+          test abc != abc || export test_result=${test_result}n ; \
+          test && export test_result=wrong");
+    assert_eq!(var("test_result"), "dfisn");
+
+    eval("export cond_result= ; \
+          [[ ./tests/files/tmp.txt == *.txt && ! -d ./tests/files/tmp.txt ]] && export cond_result=${cond_result}g ; \
+          [[ ab == 'a*' ]] || export cond_result=${cond_result}q ; \
+          [[ ( 2 -ge 3 || b > a ) ]] && export cond_result=${cond_result}o");
+    assert_eq!(var("cond_result"), "gqo");
+
+    eval("export rematch_result= ; [[ foo-123 =~ ^([a-z]+)-([0-9]+)$ ]] && \
+          export rematch_result=${BASH_REMATCH[2]}:${BASH_REMATCH[1]}:$BASH_REMATCH:${#BASH_REMATCH[@]}");
+    assert_eq!(var("rematch_result"), "123:foo:foo-123:3");
+
+    eval("export short_circuit_result=unchanged ; [ -d ./does_not_exist ] && export short_circuit_result=wrong ; \
+          [ -d . ] || export short_circuit_result=wrong");
+    assert_eq!(var("short_circuit_result"), "unchanged");
+
+    assert_error("[ -d . ", vec!["Expected ] at the end of [".to_string()]);
+    assert_error("test 1 -eq one", vec!["Expected an integer but found one".to_string()]);
+}
+
+#[test]
+fn interpret_aliases_test() {
+    use shell::utils::parse_with_aliases;
+
+    let program_dir = get_program_dir();
+    let mut interpreter = Interpreter::new(&program_dir).with_capture();
+    let mut eval = |input: &str| {
+        let ast = parse_with_aliases(&(input.to_string() + "\n"), &program_dir, interpreter.aliases()).unwrap();
+        interpreter.eval(&ast)
+    };
+
+    eval("store() { export $1=$2 ; }");
+    eval("alias remember='store alias_result' again=\"remember twice\" quote=\"it's\" spaced=\"'a b'\" nested='remember '");
+    assert_eq!(eval("alias again").1, vec!["alias again='remember twice'".to_string()]);
+    assert_eq!(eval("alias quote").1, vec!["alias quote='it'\\''s'".to_string()]);
+
+    eval("remember once");
+    assert_eq!(std::env::var("alias_result").unwrap(), "once");
+    eval("again");
+    assert_eq!(std::env::var("alias_result").unwrap(), "twice");
+    eval("nested spaced");
+    assert_eq!(std::env::var("alias_result").unwrap(), "a b");
+
+    assert_eq!(eval("unalias again nope").0, vec!["Alias nope not found".to_string()]);
+    assert_eq!(eval("alias again").0, vec!["Alias again not found".to_string()]);
+    eval("unalias -a");
+    assert_eq!(eval("alias").1, Vec::<String>::new());
+}
+
+#[test]
+fn interpret_source_test() {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    let script = std::env::temp_dir().join("shell_source_test.sh");
+    std::fs::write(&script, "# shared definitions\n\
+                             store() {\n  export $1=$2\n}\n\
+                             alias remember='store source_result'\n\
+                             remember $source_result[$#:$1]\n\
+                             record() {\n  remember $source_result[record]\n}\n\
+                             if [ $# -gt 1 ]\nthen\n  return 1\nfi\n\
+                             remember $source_result[end]\n").unwrap();
+    let script = script.display().to_string();
+
+    eval(&format!("export source_result='' ; source {} ; record", script));
+    assert_eq!(var("source_result"), "[0:][end][record]");
+
+    eval(&format!("export source_result='' ; . {} a b || export source_result=$source_result[failed]", script));
+    assert_eq!(var("source_result"), "[2:a][failed]");
+
+    assert_error("source ./does_not_exist", vec!["Could not read file: ./does_not_exist".to_string()]);
+}
+
+#[test]
+fn interpret_builtins_test() {
+    let output = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+
+    assert_output("echo a   b; echo -n c; echo -e 'd\\te\\cf'; echo -E 'g\\th'",
+                  output(&["a b", "c", "d\te", "g\\th"]));
+    assert_output("printf '%5s|%-4s|%05d|%x|%#o|%.2f|%e|%g\\n' ab cd 42 255 8 3.14159 12345.678 0.0001",
+                  output(&["   ab|cd  |00042|ff|010|3.14|1.234568e+04|0.0001"]));
+    assert_output("printf '%s=%d\\n' a 1 b 2; printf '%q %b|%c\\n' 'a b' 'x\\ty' word",
+                  output(&["a=1\nb=2", "'a b' x\ty|w"]));
+    assert_output("printf -v formatted '%03d' 7; echo $formatted", output(&["007"]));
+    assert_error("printf '%d' abc", output(&["abc is not a valid number"]));
+
+    assert_output("true && echo yes; false || echo no; : && echo colon", output(&["yes", "no", "colon"]));
+    assert_output("type echo 'if'; type -t true", output(&["echo is a shell builtin\nif is a shell keyword", "builtin"]));
+    assert_error("type does_not_exist", output(&["does_not_exist not found"]));
+    assert_output("echo() { builtin echo wrapped $1; }; echo a; command echo b; command -v echo",
+                  output(&["wrapped a", "b", "echo"]));
+    assert_error("builtin does_not_exist", output(&["does_not_exist is not a shell builtin"]));
+
+    let directory = std::env::current_dir().unwrap().canonicalize().unwrap().display().to_string();
+    assert_output("pwd -P", output(&[&directory]));
+}
+
+#[test]
+fn interpret_cd_test() {
+    let directory = std::env::current_dir().unwrap().canonicalize().unwrap();
+    let name = directory.file_name().unwrap().to_str().unwrap().to_string();
+    let directory = directory.display().to_string();
+    let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+
+    assert_output(&format!("(cd {0}/tests/.. ; pwd ; echo $OLDPWD ; cd - ; HOME={0} ; cd ; pwd)", directory),
+                  lines(&[&directory, &directory, &directory, &directory]));
+    assert_output(&format!("(CDPATH={0}/.. ; cd {1} ; cd ./tests/.. ; pwd)", directory, name),
+                  lines(&[&directory, &directory]));
+    assert_output(&format!("(HOME={0} ; pushd ./tests/.. ; pushd -0 ; dirs -l -v ; popd)", directory),
+                  lines(&["~ ~", "~ ~", &format!(" 0  {0}\n 1  {0}", directory), "~"]));
+    assert_output(&format!("(HOME={0}/tes ; cd tests ; dirs ; HOME={0}/ ; dirs)", directory),
+                  lines(&[&format!("{}/tests", directory), "~/tests"]));
+    assert_eq!(abbreviate_home("/rootfoo", "/root"), "/rootfoo");
+    assert_eq!(abbreviate_home("/root/foo", "/root/"), "~/foo");
+    assert_eq!(abbreviate_home("/root", "/root/"), "~");
+    assert_eq!(abbreviate_home("/root", "/"), "/root");
+
+    assert_error("cd a b", vec!["Cd expects at most one directory but found 2".to_string()]);
+    assert_error("popd", vec!["Directory stack is empty".to_string()]);
+    assert_error("pushd +3", vec!["+3: directory stack index out of range".to_string()]);
+}
+
+#[test]
+fn interpret_clear_test() {
+    assert_output("echo a ; clear && clear -x && reset && echo b", vec!["a".to_string(), "b".to_string()]);
+    assert_output("clear | cat ; clear > /dev/null && echo redirected", vec!["redirected".to_string()]);
+    assert_error("clear -y", vec!["-y is not a valid option for clear".to_string()]);
+}
+
+#[test]
+fn interpret_set_options_test() {
+    let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+
+    assert_output("X=1 ; A=$X B=${X}2 ; echo $A $B", lines(&["1 12"]));
+    assert_output("show() { echo $PREFIX ; } ; PREFIX=inner show ; echo \"[$PREFIX]\"", lines(&["inner", "[]"]));
+    eval("exported_variable=value ; export exported_variable ; export other_exported='a b' ; unset nothing");
+    assert_eq!(std::env::var("exported_variable").unwrap(), "value");
+    assert_eq!(std::env::var("other_exported").unwrap(), "a b");
+    eval("unset other_exported");
+    assert!(std::env::var("other_exported").is_err());
+
+    assert_output("set -- a 'b c' ; echo $# $2 ; set x ; echo $# $1", lines(&["2 b c", "1 x"]));
+    assert_output("(set -e ; false || true ; ! true ; if false ; then true ; fi ; echo reached ; false ; echo no) ; echo $?",
+                  lines(&["reached", "1"]));
+    assert_output("(set -o pipefail ; false | true) || echo failed ; false | true && echo passed", lines(&["failed", "passed"]));
+    assert_output("set -eo pipefail ; set -o", lines(&["errexit        \ton\nignoreeof      \toff\nnoexec         \toff\nnounset        \toff\n\
+                                                        pipefail       \ton\nverbose        \toff\nxtrace         \toff"]));
+    assert_output("set -u +o nounset -x ; set +o", lines(&["set +o errexit\nset +o ignoreeof\nset +o noexec\nset +o nounset\n\
+                                                         set +o pipefail\nset +o verbose\nset -o xtrace"]));
+
+    assert_error("set -u ; echo $undefined_variable ; echo after", lines(&["undefined_variable: unbound variable"]));
+    assert_error("PS4='>> ' ; set -x ; echo 'a b' ; set +x", lines(&[">> echo 'a b'", ">> set +x"]));
+    assert_error("set -q", lines(&["-q is not a valid option for set"]));
+    assert_error("set -o bogus", lines(&["bogus is not a valid option name"]));
+
+    let script = std::env::temp_dir().join("shell_set_options_test.sh");
+    std::fs::write(&script, "set -v\necho shown\nset +v -n\necho hidden\n").unwrap();
+    let mut interpreter = Interpreter::new(&get_program_dir()).with_capture();
+    let (errors, output) = interpreter.eval_file(&script.display().to_string());
+    assert_eq!(errors, lines(&["echo shown", "set +v -n"]));
+    assert_eq!(output, lines(&["shown"]));
+}
+
+#[test]
+fn interpret_prompt_test() {
+    let directory = std::env::current_dir().unwrap();
+    let name = directory.file_name().unwrap().to_str().unwrap().to_string();
+    let mut interpreter = Interpreter::new(&get_program_dir()).with_capture();
+    let mut eval = |interpreter: &mut Interpreter, input: &str| {
+        let ast = parse(&(input.to_string() + "\n"), &get_program_dir()).unwrap();
+        interpreter.eval(&ast)
+    };
+
+    assert_eq!(interpreter.prompt("PS2"), "> ");
+    assert_eq!(interpreter.prompt("PS4"), "+ ");
+    eval(&mut interpreter, "PS1='\\[\\e[1m\\]\\W \\101\\\\ $(echo \"a b\")$prompt_suffix\\n\\?> ' ; prompt_suffix=! ; false");
+    assert_eq!(interpreter.prompt("PS1"), format!("\x1b[1m{} A\\ a b!\n1> ", name));
+
+    eval(&mut interpreter, "PROMPT_COMMAND='export prompt_command_result=ran ; true' ; PS1='$(false)\\?' ; false");
+    interpreter.run_prompt_command();
+    assert_eq!(std::env::var("prompt_command_result").unwrap(), "ran");
+    assert_eq!(interpreter.prompt("PS1"), "1");
+    assert_eq!(eval(&mut interpreter, "PS4='[\\?]' ; set -x ; true ; set +x").0, vec!["[0]true", "[0]set +x"]);
+    eval(&mut interpreter, "PS1='\\?> ' ; (exit 42)");
+    assert_eq!(interpreter.prompt("PS1"), "42> ");
+}
+
+#[test]
+fn interpret_parse_error_test() {
+    let script = std::env::temp_dir().join("shell_parse_error_test.sh");
+    std::fs::write(&script, "echo first\n\nif true\nthen echo b ; done\necho never\n").unwrap();
+    let mut interpreter = Interpreter::new(&get_program_dir()).with_capture();
+    let (errors, output) = interpreter.eval_file(&script.display().to_string());
+    assert_eq!(errors, vec!["Unexpected Done\n  |\n4 | then echo b ; done\n  |               ^^^^".to_string()]);
+    assert_eq!(output, vec!["first".to_string()]);
+}
+
+#[test]
+fn interpret_structured_errors_test() {
+    use shell::error::{ExecError, ExpandError, ShellError};
+    use std::io::ErrorKind;
+
+    let mut interpreter = Interpreter::new(&get_program_dir()).with_capture();
+    let mut eval = |interpreter: &mut Interpreter, input: &str| {
+        let ast = parse(&(input.to_string() + "\n"), &get_program_dir()).unwrap();
+        interpreter.eval(&ast)
+    };
+
+    let (errors, _) = eval(&mut interpreter, "echo < ./does_not_exist.txt");
+    assert_eq!(errors, vec!["File does not exist: ./does_not_exist.txt".to_string()]);
+    assert_eq!(interpreter.errors(), &[ShellError::Exec(ExecError::RedirectInput {
+        path: "./does_not_exist.txt".to_string(),
+        kind: ErrorKind::NotFound,
+    })]);
+
+    eval(&mut interpreter, "set -u ; echo $missing_variable");
+    assert_eq!(interpreter.errors(), &[ShellError::Expand(ExpandError::UnboundVariable("missing_variable".to_string()))]);
+
+    eval(&mut interpreter, "set +u ; echo > ./does_not_exist/out.txt");
+    assert!(matches!(interpreter.errors(), [ShellError::Exec(ExecError::RedirectOutput { .. })]));
+
+    eval(&mut interpreter, "cd a b");
+    assert_eq!(interpreter.errors(), &[ShellError::Exec(ExecError::Builtin("Cd expects at most one directory but found 2".to_string()))]);
+
+    let (errors, _) = eval(&mut interpreter, "PROMPT_COMMAND='if then' ; true");
+    assert!(errors.is_empty());
+    interpreter.run_prompt_command();
+    assert!(matches!(interpreter.errors(), [ShellError::Parse(_)]));
+
+    let error = ShellError::from(ExecError::CommandNotFound("./programs/nothing".to_string()));
+    assert_eq!(error.to_string(), "Command not found: ./programs/nothing");
+    assert!(std::error::Error::source(&error).is_some());
+}
@@ -59,9 +59,11 @@ fn lex_double_hyphens_test() {
 fn lex_builtin_commands_test() {
     let expected_tokens = vec![
         Token::Command("cd".to_string()),
+        Token::Semicolon,
         Token::Command("clear".to_string()),
     ];
-    assert_eq!(get_tokens("Cd Clear"), expected_tokens);
+    assert_eq!(get_tokens("Cd ; Clear"), expected_tokens);
+    assert_eq!(get_tokens("echo Hello Test True Source")[1..], ["Hello", "Test", "True", "Source"].map(|word| Token::Argument(word.to_string())));
 }
 
 #[test]
@@ -80,4 +82,217 @@ fn lex_complex_command_test() {
         Token::Argument("hello".to_string()),
     ];
     assert_eq!(get_tokens("cat a.txt | grep h &&seq 3 ; echo hello"), expected_tokens);
-}
\ No newline at end of file
+}
+#[test]
+fn lex_quotes_test() {
+    let expected_tokens = vec![
+        Token::Argument("'Keep Case'".to_string()),
+        Token::Argument("\"a|b;c\"".to_string()),
+        Token::Argument("Mixed'CASE'".to_string()),
+    ];
+    assert_eq!(get_tokens("'Keep Case' \"a|b;c\" Mixed'CASE'"), expected_tokens);
+}
+
+#[test]
+fn lex_loops_test() {
+    let expected_tokens = vec![
+        Token::For,
+        Token::Argument("Name".to_string()),
+        Token::Argument("in".to_string()),
+        Token::Argument("A".to_string()),
+        Token::Semicolon,
+        Token::Do,
+        Token::Command("break".to_string()),
+        Token::Semicolon,
+        Token::Done,
+        Token::Semicolon,
+        Token::For,
+        Token::Arithmetic("i = 0; i < 3; i++".to_string()),
+        Token::Semicolon,
+        Token::While,
+        Token::EOL,
+        Token::Until,
+    ];
+    assert_eq!(get_tokens("For Name in A; Do break; done; for (( i = 0; i < 3; i++ )); while\nuntil"), expected_tokens);
+}
+
+#[test]
+fn lex_case_test() {
+    let expected_tokens = vec![
+        Token::Case,
+        Token::EnvVariable("x".to_string()),
+        Token::Argument("in".to_string()),
+        Token::Argument("[A-Z]*".to_string()),
+        Token::Pipe,
+        Token::Argument("a-b".to_string()),
+        Token::RightParen,
+        Token::DoubleSemicolon,
+        Token::SemicolonAmpersand,
+        Token::DoubleSemicolonAmpersand,
+        Token::Esac,
+    ];
+    assert_eq!(get_tokens("case $x in [A-Z]*|a-b) ;; ;& ;;& esac"), expected_tokens);
+}
+
+#[test]
+fn lex_functions_test() {
+    let expected_tokens = vec![
+        Token::Argument("greet".to_string()),
+        Token::LeftParen,
+        Token::RightParen,
+        Token::LeftBrace,
+        Token::Command("local".to_string()),
+        Token::Argument("name=$1".to_string()),
+        Token::Semicolon,
+        Token::Command("return".to_string()),
+        Token::Argument("2".to_string()),
+        Token::Semicolon,
+        Token::RightBrace,
+        Token::Semicolon,
+        Token::Function,
+        Token::Argument("greet".to_string()),
+        Token::LeftBrace,
+        Token::RightBrace,
+    ];
+    assert_eq!(get_tokens("Greet() { local name=$1; return 2; }; function greet { }"), expected_tokens);
+}
+
+#[test]
+fn lex_negation_and_time_test() {
+    let expected_tokens = vec![
+        Token::Bang,
+        Token::Command("cd".to_string()),
+        Token::Argument("!".to_string()),
+        Token::Semicolon,
+        Token::Time,
+        Token::Hyphen("-p".to_string()),
+        Token::Command("cd".to_string()),
+        Token::Argument("time".to_string()),
+    ];
+    assert_eq!(get_tokens("! cd !; time -p cd time"), expected_tokens);
+}
+
+#[test]
+fn lex_conditionals_test() {
+    let expected_tokens = vec![
+        Token::Command("[".to_string()),
+        Token::Hyphen("-f".to_string()),
+        Token::Argument("'a b'".to_string()),
+        Token::Argument("]".to_string()),
+        Token::DoubleAmpersand,
+        Token::Conditional("$x =~ ^(a|b)$ && \"]]\" < y".to_string()),
+        Token::DoublePipe,
+        Token::Command("test".to_string()),
+        Token::Argument("[[".to_string()),
+    ];
+    assert_eq!(get_tokens("[ -f 'a b' ] && [[ $x =~ ^(a|b)$ && \"]]\" < y ]] || test [["), expected_tokens);
+}
+
+#[test]
+fn lex_aliases_test() {
+    use std::collections::HashMap;
+
+    let aliases = HashMap::from([
+        ("up".to_string(), "cd ..".to_string()),
+        ("loop".to_string(), "loop again".to_string()),
+        ("first".to_string(), "second x".to_string()),
+        ("second".to_string(), "first y".to_string()),
+        ("run".to_string(), "set ".to_string()),
+        ("key".to_string(), "value".to_string()),
+    ]);
+    let get_tokens = |input: &str| Lexer::new(input, &get_program_dir()).with_aliases(&aliases).get_tokens();
+
+    let expected_tokens = vec![
+        Token::Command("cd".to_string()),
+        Token::Argument("..".to_string()),
+        Token::Semicolon,
+        Token::Command("cd".to_string()),
+        Token::Argument("up".to_string()),
+        Token::Semicolon,
+        Token::Argument("'up'".to_string()),
+    ];
+    assert_eq!(get_tokens("up; cd up; 'up'"), expected_tokens);
+
+    let expected_tokens = vec![
+        Token::Argument("loop".to_string()),
+        Token::Argument("again".to_string()),
+        Token::Semicolon,
+        Token::Argument("first".to_string()),
+        Token::Argument("y".to_string()),
+        Token::Argument("x".to_string()),
+    ];
+    assert_eq!(get_tokens("loop; first"), expected_tokens);
+
+    let expected_tokens = vec![
+        Token::Command("set".to_string()),
+        Token::Argument("value".to_string()),
+        Token::Argument("key".to_string()),
+    ];
+    assert_eq!(get_tokens("run key key"), expected_tokens);
+}
+
+#[test]
+fn lex_comments_test() {
+    let expected_tokens = vec![
+        Token::Command("cd".to_string()),
+        Token::Argument("a#b".to_string()),
+        Token::EOL,
+        Token::EOL,
+        Token::Command("cd".to_string()),
+        Token::EnvVariable("#".to_string()),
+    ];
+    assert_eq!(get_tokens("cd a#b # comment ; cd c\n# whole line\ncd $#"), expected_tokens);
+}
+
+#[test]
+fn lex_assignments_test() {
+    let expected_tokens = vec![
+        Token::Argument("Name=Value".to_string()),
+        Token::Argument("Other='a b'".to_string()),
+        Token::Command("export".to_string()),
+        Token::Argument("Name=X".to_string()),
+        Token::Semicolon,
+        Token::Command("set".to_string()),
+        Token::Hyphen("-e".to_string()),
+        Token::Argument("a=b".to_string()),
+    ];
+    assert_eq!(get_tokens("Name=Value Other='a b' Export Name=X; set -e a=b"), expected_tokens);
+}
+
+#[test]
+fn lex_spans_test() {
+    use shell::token::{Span, SpannedToken};
+
+    let program_dir = get_program_dir();
+    let mut lexer = Lexer::new("cd  dir ;\n  exit 1", &program_dir);
+    let span = |start, end, line, column| Span { start, end, line, column };
+    let expected_tokens = vec![
+        SpannedToken { token: Token::Command("cd".to_string()), span: span(0, 2, 1, 1) },
+        SpannedToken { token: Token::Argument("dir".to_string()), span: span(4, 7, 1, 5) },
+        SpannedToken { token: Token::Semicolon, span: span(8, 9, 1, 9) },
+        SpannedToken { token: Token::EOL, span: span(9, 10, 1, 10) },
+        SpannedToken { token: Token::Command("exit".to_string()), span: span(12, 16, 2, 3) },
+        SpannedToken { token: Token::Argument("1".to_string()), span: span(17, 18, 2, 8) },
+    ];
+    assert_eq!(lexer.get_spanned_tokens(), expected_tokens);
+}
+
+#[test]
+fn lex_keywords_as_arguments_test() {
+    let arguments = |words: &[&str]| words.iter().map(|word| Token::Argument(word.to_string())).collect::<Vec<Token>>();
+
+    assert_eq!(get_tokens("echo done }")[1..], arguments(&["done", "}"]));
+    assert_eq!(get_tokens("echo for while in esac function")[1..], arguments(&["for", "while", "in", "esac", "function"]));
+    assert_eq!(get_tokens("echo a { b DONE")[1..], arguments(&["a", "{", "b", "DONE"]));
+    assert_eq!(get_tokens("echo x ; done"), vec![Token::Command("echo".to_string()), Token::Argument("x".to_string()),
+                                                 Token::Semicolon, Token::Done]);
+    assert_eq!(get_tokens("f() { cd ; }")[3], Token::LeftBrace);
+    assert_eq!(get_tokens("case x in esac")[3], Token::Esac);
+    assert_eq!(get_tokens("for x do echo do")[2..], vec![Token::Do, Token::Command("echo".to_string()), Token::Argument("do".to_string())]);
+    assert_eq!(get_tokens("echo else then")[1..], arguments(&["else", "then"]));
+    assert_eq!(get_tokens("if true; then echo then; fi")[5], Token::Argument("then".to_string()));
+    assert_eq!(get_tokens("if false; then :; else echo else; fi")[6..8], [Token::Else, Token::Command("echo".to_string())]);
+    assert_eq!(get_tokens("if false; then :; else echo else; fi")[8], Token::Argument("else".to_string()));
+    assert_eq!(get_tokens("if true then echo then else echo else")[2..], vec![Token::Then, Token::Command("echo".to_string()),
+        Token::Argument("then".to_string()), Token::Else, Token::Command("echo".to_string()), Token::Argument("else".to_string())]);
+}
@@ -3,6 +3,7 @@
 use std::fs::File;
 use shell::ast::{Expr, Operator, Redirect};
 use shell::ast::Expr::{Binary, Cmd};
+use shell::error::ParseError;
 use shell::utils::{get_program_dir, parse};
 use std::sync::Once;
 
@@ -16,7 +17,7 @@ fn initialize() {
     })
 }
 
-fn parse_input(input: &str) -> Result<Expr, String> {
+fn parse_input(input: &str) -> Result<Expr, ParseError> {
     let program_dir = get_program_dir();
     parse(input, &program_dir)
 }
@@ -152,3 +153,218 @@ fn parse_complex_binary_with_redirects_test() {
     assert_eq!(parse_input("cat ./f < input.txt | fmt & echo ok ; echo next > a.txt").unwrap(), expected_ast);
 }
 
+
+#[test]
+fn parse_groups_test() {
+    let cd = |dir: &str| Cmd {
+        name: "cd".to_string(),
+        arguments: vec![dir.to_string()],
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let expected_ast = Binary(
+        Box::new(Expr::Subshell(
+            Box::new(Binary(Box::new(cd("tests")), Operator::Next, Box::new(cd("files")))),
+            Redirect::new(None, Some("out.txt".to_string()), None),
+        )),
+        Operator::LogicAnd,
+        Box::new(Expr::Group(
+            Box::new(cd("tests")),
+            Redirect::new(None, None, Some("err.txt".to_string())),
+        )),
+    );
+    assert_eq!(parse_input("(cd tests; cd files) > out.txt && { cd tests; } 2> err.txt").unwrap(), expected_ast);
+    assert!(parse_input("(cd tests").is_err());
+    assert!(parse_input("{ cd tests ;").is_err());
+}
+
+#[test]
+fn parse_loops_test() {
+    let cd = |dir: &str| Cmd {
+        name: "cd".to_string(),
+        arguments: vec![dir.to_string()],
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let expected_ast = Expr::For(
+        "dir".to_string(),
+        Some(vec!["a".to_string(), "'b c'".to_string(), "$HOME".to_string()]),
+        Box::new(Binary(Box::new(cd("$dir")), Operator::Next, Box::new(cd("..")))),
+    );
+    assert_eq!(parse_input("for dir in a 'b c' $HOME; do cd $dir; cd ..; done").unwrap(), expected_ast);
+
+    let expected_ast = Expr::For("x".to_string(), None, Box::new(cd("$x")));
+    assert_eq!(parse_input("for x; do cd $x; done").unwrap(), expected_ast);
+    assert_eq!(parse_input("for x do cd $x; done").unwrap(), expected_ast);
+    assert_eq!(parse_input("for x\ndo cd $x; done").unwrap(), expected_ast);
+
+    let expected_ast = Expr::ArithmeticFor(
+        "i=0".to_string(),
+        "i<3".to_string(),
+        "i++".to_string(),
+        Box::new(cd("$i")),
+    );
+    assert_eq!(parse_input("for ((i=0;i<3;i++)) do cd $i; done").unwrap(), expected_ast);
+
+    let expected_ast = Expr::While(Box::new(cd("a")), Box::new(cd("b")));
+    assert_eq!(parse_input("while cd a; do cd b; done").unwrap(), expected_ast);
+
+    let expected_ast = Expr::Until(Box::new(cd("a")), Box::new(cd("b")));
+    assert_eq!(parse_input("until cd a; do cd b; done").unwrap(), expected_ast);
+
+    assert!(parse_input("while cd a; do cd b").is_err());
+    assert!(parse_input("for x a b; do cd b; done").is_err());
+
+    let expected_ast = Binary(Box::new(Expr::Arithmetic("1 < 2".to_string())), Operator::LogicAnd, Box::new(cd("a")));
+    assert_eq!(parse_input("(( 1 < 2 )) && cd a").unwrap(), expected_ast);
+}
+
+#[test]
+fn parse_case_test() {
+    use shell::ast::{CaseArm, CaseTerminator};
+
+    let cd = |dir: &str| Cmd {
+        name: "cd".to_string(),
+        arguments: vec![dir.to_string()],
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let expected_ast = Expr::Case("$dir".to_string(), vec![
+        CaseArm {
+            patterns: vec!["a*".to_string(), "'b c'".to_string()],
+            body: Some(cd("a")),
+            terminator: CaseTerminator::FallThrough,
+        },
+        CaseArm {
+            patterns: vec!["[0-9]".to_string()],
+            body: None,
+            terminator: CaseTerminator::Continue,
+        },
+        CaseArm {
+            patterns: vec!["*".to_string()],
+            body: Some(cd("b")),
+            terminator: CaseTerminator::Break,
+        },
+    ]);
+    assert_eq!(parse_input("case $dir in a*|'b c') cd a ;& ([0-9]) ;;& *) cd b ;; esac").unwrap(), expected_ast);
+    assert_eq!(parse_input("case x in esac").unwrap(), Expr::Case("x".to_string(), vec![]));
+    assert!(parse_input("case x in a) cd a ;;").is_err());
+}
+
+#[test]
+fn parse_elif_test() {
+    let cd = |dir: &str| Cmd {
+        name: "cd".to_string(),
+        arguments: vec![dir.to_string()],
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let expected_ast = Expr::IfElse(
+        Box::new(cd("a")),
+        Box::new(cd("b")),
+        Box::new(Expr::IfElse(Box::new(cd("c")), Box::new(cd("d")), Box::new(cd("e")))),
+    );
+    assert_eq!(parse_input("if cd a; then cd b; elif cd c; then cd d; else cd e; fi").unwrap(), expected_ast);
+    assert_eq!(parse_input("if cd a\nthen\n  cd b\nelif cd c\nthen\n  cd d\nelse\n  cd e\nfi\n").unwrap(), expected_ast);
+
+    let expected_ast = Expr::If(Box::new(cd("a")), Box::new(Binary(Box::new(cd("b")), Operator::Next, Box::new(cd("c")))));
+    assert_eq!(parse_input("if cd a; then\n cd b\n cd c\nfi").unwrap(), expected_ast);
+
+    assert!(parse_input("if cd a; then cd b; elif cd c; then cd d").is_err());
+}
+
+#[test]
+fn parse_functions_test() {
+    let cmd = |name: &str, arguments: Vec<&str>| Cmd {
+        name: name.to_string(),
+        arguments: arguments.iter().map(|argument| argument.to_string()).collect(),
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let body = Expr::Group(
+        Box::new(Binary(Box::new(cmd("local", vec!["dir=$1"])), Operator::Next, Box::new(cmd("cd", vec!["$dir"])))),
+        Redirect::new(None, None, None),
+    );
+    let expected_ast = Expr::Function("go".to_string(), Box::new(body.clone()));
+    assert_eq!(parse_input("go() { local dir=$1; cd $dir; }").unwrap(), expected_ast);
+    assert_eq!(parse_input("function go { local dir=$1; cd $dir; }").unwrap(), expected_ast);
+    assert_eq!(parse_input("function go ()\n{\n  local dir=$1\n  cd $dir\n}").unwrap(), expected_ast);
+
+    let expected_ast = Binary(Box::new(expected_ast), Operator::Next, Box::new(cmd("go", vec!["tests"])));
+    assert_eq!(parse_input("go() { local dir=$1; cd $dir; }; go tests").unwrap(), expected_ast);
+
+    assert!(parse_input("go() cd tests").is_err());
+    assert!(parse_input("function { cd tests; }").is_err());
+}
+
+#[test]
+fn parse_negation_and_time_test() {
+    let cd = |dir: &str| Cmd {
+        name: "cd".to_string(),
+        arguments: vec![dir.to_string()],
+        redirect: Redirect::new(None, None, None),
+    };
+
+    let expected_ast = Binary(
+        Box::new(Expr::Not(Box::new(Binary(Box::new(cd("a")), Operator::Pipe, Box::new(cd("b")))))),
+        Operator::LogicAnd,
+        Box::new(cd("c")),
+    );
+    assert_eq!(parse_input("! cd a | cd b && cd c").unwrap(), expected_ast);
+
+    let expected_ast = Expr::Time(Box::new(Expr::Not(Box::new(cd("a")))), true);
+    assert_eq!(parse_input("time -p ! cd a").unwrap(), expected_ast);
+
+    let expected_ast = Binary(
+        Box::new(Expr::Time(Box::new(Binary(Box::new(cd("a")), Operator::Pipe, Box::new(cd("b")))), false)),
+        Operator::Next,
+        Box::new(cd("c")),
+    );
+    assert_eq!(parse_input("time cd a | cd b; cd c").unwrap(), expected_ast);
+
+    assert!(parse_input("!").is_err());
+}
+
+#[test]
+fn parse_conditionals_test() {
+    use shell::ast::Condition;
+
+    let word = |word: &str| word.to_string();
+    let expected_ast = Expr::Conditional(Condition::Or(
+        Box::new(Condition::And(
+            Box::new(Condition::Not(Box::new(Condition::Unary(word("-f"), word("$file"))))),
+            Box::new(Condition::Binary(word("$x"), word("=~"), word("^(a|b)( x)*$"))),
+        )),
+        Box::new(Condition::Unary(word("-n"), word("'a b'"))),
+    ));
+    assert_eq!(parse_input("[[ ! -f $file && $x =~ ^(a|b)( x)*$ || 'a b' ]]").unwrap(), expected_ast);
+
+    let expected_ast = Expr::Conditional(Condition::And(
+        Box::new(Condition::Binary(word("$x"), word("=="), word("a*"))),
+        Box::new(Condition::Or(
+            Box::new(Condition::Binary(word("1"), word("-lt"), word("2"))),
+            Box::new(Condition::Unary(word("-d"), word("."))),
+        )),
+    ));
+    assert_eq!(parse_input("[[ $x == a* && ( 1 -lt 2 || -d . ) ]]").unwrap(), expected_ast);
+
+    assert!(parse_input("[[ ( -d . ]]").is_err());
+    assert!(parse_input("[[ a & b ]]").is_err());
+    assert!(parse_input("[[ ]]").is_err());
+}
+
+#[test]
+fn parse_errors_test() {
+    use shell::token::{Span, Token};
+
+    let error = parse_input("echo a\nif true; then echo b; done").unwrap_err();
+    assert_eq!(error.span, Span { start: 29, end: 33, line: 2, column: 23 });
+    assert_eq!(error.found, Some(Token::Done));
+    assert_eq!(error.render("echo a\nif true; then echo b; done"),
+               "Unexpected Done\n  |\n2 | if true; then echo b; done\n  |                       ^^^^");
+
+    let error = parse_input("(cd tests").unwrap_err();
+    assert_eq!(error.expected, vec![Token::RightParen]);
+    assert_eq!(error.found, None);
+    assert_eq!(error.to_string(), "Unexpected end of input");
+}
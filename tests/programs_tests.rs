@@ -0,0 +1,37 @@
+use std::process::Command;
+
+fn run(program: &str, arguments: &[&str]) -> (i32, String) {
+    let output = Command::new(program).args(arguments).output().unwrap();
+    (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn programs_help_test() {
+    let (status, help) = run(env!("CARGO_BIN_EXE_cat"), &["--help"]);
+    assert_eq!(status, 0);
+    assert_eq!(help, "Usage: cat [OPTION]... [FILE]...\n\
+                      Concatenate files to the standard output.\n\n\
+                      Options:\n\
+                      \x20 -b          Omit empty lines\n\
+                      \x20 -n          Number all output lines\n\
+                      \x20 --help      Display this help and exit\n\
+                      \x20 --version   Display version information and exit\n");
+
+    for program in [env!("CARGO_BIN_EXE_eq"), env!("CARGO_BIN_EXE_neq")] {
+        let (status, help) = run(program, &["--help"]);
+        assert_eq!(status, 0);
+        assert!(help.starts_with("Usage: ") && help.contains(" STRING STRING...\n"));
+    }
+}
+
+#[test]
+fn programs_version_test() {
+    let version = env!("CARGO_PKG_VERSION");
+    assert_eq!(run(env!("CARGO_BIN_EXE_cat"), &["--version"]), (0, format!("cat (shell) {}\n", version)));
+    assert_eq!(run(env!("CARGO_BIN_EXE_eq"), &["--version"]), (0, format!("eq (shell) {}\n", version)));
+    assert_eq!(run(env!("CARGO_BIN_EXE_neq"), &["--version"]), (0, format!("neq (shell) {}\n", version)));
+
+    assert_eq!(run(env!("CARGO_BIN_EXE_eq"), &["a", "a"]).0, 0);
+    assert_eq!(run(env!("CARGO_BIN_EXE_neq"), &["a", "a"]).0, 1);
+    assert_eq!(run(env!("CARGO_BIN_EXE_eq"), &["--", "--version"]).0, 1);
+}
@@ -0,0 +1,310 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use shell::builtins::{Builtin, ExitStatus, INTERNAL};
+use shell::environment::Environment;
+use shell::error::{ExecError, ShellError};
+use shell::interpreter::Interpreter;
+use shell::shell::{ExecResult, Shell};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(name);
+    std::fs::create_dir_all(directory.join("nested")).unwrap();
+    directory.canonicalize().unwrap()
+}
+
+#[test]
+fn shell_run_test() {
+    let mut shell = Shell::builder().clear_env().build();
+    let result = shell.run("echo 'a  ' ; printf 'b\\n\\n'");
+    assert_eq!(result, ExecResult { status: 0, stdout: b"a  \nb\n\n".to_vec(), stderr: vec![] });
+    assert_eq!(shell.run("printf '\\xff\\101\\377%b' '\\xfe' ; echo -e '\\x80'").stdout, b"\xffA\xff\xfe\x80\n".to_vec());
+
+    let result = shell.run("cd ./does_not_exist");
+    assert_eq!(result.status, 1);
+    assert_eq!(result.stderr_lossy(), "./does_not_exist is not a valid directory\n");
+
+    let result = shell.run("echo before ; exit 3 ; echo after");
+    assert_eq!((result.status, result.stdout_lossy()), (3, "before\n".to_string()));
+
+    assert_eq!(shell.run("f() { return 4 ; } ; f").status, 4);
+    assert_eq!(shell.run("(exit 5) ; exit").status, 5);
+    assert_eq!(shell.run("(exit 6)").status, 6);
+
+    let result = Shell::builder().build().run("trap 'echo bye' EXIT ; exit 2");
+    assert_eq!((result.status, result.stdout_lossy()), (2, "bye\n".to_string()));
+
+    let result = shell.run("if true ; then echo x ; done");
+    assert_eq!(result.status, 2);
+    assert!(matches!(shell.errors(), [ShellError::Parse(_)]));
+
+    let result = shell.run("echo < ./does_not_exist.txt");
+    assert!(!result.success());
+    assert!(matches!(shell.errors(), [ShellError::Exec(ExecError::RedirectInput { .. })]));
+}
+
+#[test]
+fn shell_isolation_test() {
+    let first_dir = temp_dir("shell_isolation_first");
+    let second_dir = temp_dir("shell_isolation_second");
+    let process_dir = std::env::current_dir().unwrap();
+
+    let mut first = Shell::builder().cwd(&first_dir).env("SHARED", "first").var("greeting", "hi").build();
+    let mut second = Shell::builder().cwd(&second_dir).clear_env().build();
+
+    assert_eq!(first.run("cd nested ; pwd").stdout_lossy(), format!("{}\n", first_dir.join("nested").display()));
+    assert_eq!(second.run("pwd").stdout_lossy(), format!("{}\n", second_dir.display()));
+    assert_eq!(first.cwd(), first_dir.join("nested"));
+    assert_eq!(std::env::current_dir().unwrap(), process_dir);
+
+    first.run("export isolated_variable=1 ; echo > created.txt");
+    assert!(first_dir.join("nested/created.txt").is_file());
+    assert_eq!(first.var("isolated_variable"), Some("1".to_string()));
+    assert_eq!(second.var("isolated_variable"), None);
+    assert!(std::env::var("isolated_variable").is_err());
+
+    assert_eq!(first.run("echo $greeting $SHARED").stdout_lossy(), "hi first\n");
+    assert_eq!(second.run("echo [$SHARED]").stdout_lossy(), "[]\n");
+    assert_eq!(second.run("[ -d nested ] && echo found").stdout_lossy(), "found\n");
+}
+
+#[test]
+fn shell_sinks_test() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let (stdout, stderr) = (output.clone(), errors.clone());
+    let mut shell = Shell::builder()
+        .on_stdout(move |bytes| stdout.borrow_mut().extend_from_slice(bytes))
+        .on_stderr(move |bytes| stderr.borrow_mut().extend_from_slice(bytes))
+        .build();
+
+    let result = shell.run("echo one ; set -q ; echo two");
+    assert_eq!(*output.borrow(), b"one\ntwo\n".to_vec());
+    assert_eq!(*errors.borrow(), b"-q is not a valid option for set\n".to_vec());
+    assert_eq!(result.stdout, b"one\ntwo\n".to_vec());
+}
+
+struct Upper;
+
+impl Builtin for Upper {
+    fn name(&self) -> &str {
+        "upper"
+    }
+
+    fn help(&self) -> &str {
+        "upper\n    Convert the standard input to upper case."
+    }
+
+    fn run(&self, _: &mut Interpreter, arguments: &[String], stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> ExitStatus {
+        if !arguments.is_empty() {
+            let _ = writeln!(stderr, "upper takes no arguments");
+            return ExitStatus(2);
+        }
+        let mut input = String::new();
+        let _ = stdin.read_to_string(&mut input);
+        let _ = stdout.write_all(input.to_uppercase().as_bytes());
+        ExitStatus::SUCCESS
+    }
+}
+
+#[test]
+fn shell_builtin_test() {
+    let directory = temp_dir("shell_builtin");
+    let mut shell = Shell::builder().cwd(&directory).builtin(Upper).build();
+
+    assert_eq!(shell.run("echo hello | upper").stdout_lossy(), "HELLO\n");
+    assert_eq!(shell.run("echo a | upper | upper").stdout_lossy(), "A\n");
+    assert_eq!(shell.run("type upper").stdout_lossy(), "upper is a shell builtin\n");
+
+    shell.run("echo text > input.txt ; upper < input.txt > output.txt");
+    assert_eq!(std::fs::read_to_string(directory.join("output.txt")).unwrap(), "TEXT\n");
+
+    let result = shell.run("upper extra");
+    assert_eq!((result.success(), result.stderr_lossy()), (false, "upper takes no arguments\n".to_string()));
+    assert_eq!(shell.run("upper extra || echo failed").stdout_lossy(), "failed\n");
+    assert_eq!(shell.run("builtin upper < input.txt").stdout_lossy(), "TEXT\n");
+}
+
+#[test]
+fn shell_internal_handles_test() {
+    let directory = temp_dir("shell_internal_handles");
+    let mut interpreter = Interpreter::new("").with_capture().with_environment(Environment::isolated(directory.clone(), HashMap::new()));
+    let mut run = |name: &str, arguments: &[&str]| {
+        let builtin = INTERNAL.iter().find(|builtin| builtin.name() == name).unwrap();
+        let arguments = arguments.iter().map(|argument| argument.to_string()).collect::<Vec<String>>();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let status = builtin.run(&mut interpreter, &arguments, &mut std::io::empty(), &mut stdout, &mut stderr);
+        (status, String::from_utf8(stdout).unwrap())
+    };
+
+    assert_eq!(run("echo", &["-n", "a", "b"]), (ExitStatus::SUCCESS, "a b".to_string()));
+    assert_eq!(run("printf", &["%s-%s\\n", "a", "b"]).1, "a-b\n");
+    assert_eq!(run("pwd", &[]).1, format!("{}\n", directory.display()));
+    assert_eq!(run("dirs", &["-l"]).1, format!("{}\n", directory.display()));
+    assert_eq!(run("type", &["-t", "echo"]).1, "builtin\n");
+    assert!(run("help", &["groups"]).1.starts_with("{ list; }\n"));
+    run("alias", &["ll=ls -l"]);
+    assert_eq!(run("alias", &["-p"]).1, "alias ll='ls -l'\n");
+    run("trap", &["echo bye", "EXIT"]);
+    assert_eq!(run("trap", &["-p", "EXIT"]).1, "trap -- 'echo bye' EXIT\n");
+    assert!(run("set", &["-o"]).1.contains("pipefail       \toff\n"));
+    run("export", &["SHARED=1"]);
+    assert_eq!(run("export", &["-p"]).1, "export SHARED=1\n");
+    assert_eq!(run("set", &[]).1, "SHARED=1\n");
+
+    let cd = INTERNAL.iter().find(|builtin| builtin.name() == "cd").unwrap();
+    let mut stderr = vec![];
+    let status = cd.run(&mut interpreter, &["./missing".to_string()], &mut std::io::empty(), &mut std::io::sink(), &mut stderr);
+    assert_eq!((status, String::from_utf8(stderr).unwrap()), (ExitStatus::FAILURE, "./missing is not a valid directory\n".to_string()));
+}
+
+#[test]
+fn shell_builtin_pipeline_test() {
+    let directory = temp_dir("shell_builtin_pipeline");
+    let mut shell = Shell::builder().cwd(&directory).clear_env().builtin(Upper).build();
+
+    assert_eq!(shell.run("cd nested | echo piped ; pwd").stdout_lossy(), format!("piped\n{}\n", directory.display()));
+    assert_eq!(shell.run("export LEAK=1 | echo ; echo [$LEAK]").stdout_lossy(), "\n[]\n");
+    assert_eq!(shell.run("exit 3 | echo alive").status, 0);
+    assert_eq!(shell.run("pwd | upper").stdout_lossy(), format!("{}\n", directory.display().to_string().to_uppercase()));
+
+    let result = shell.run("set -q 2> errors.txt ; set -o > options.txt ; echo b | upper | upper > upper.txt");
+    assert_eq!((result.stdout, result.stderr), (vec![], vec![]));
+    assert_eq!(std::fs::read_to_string(directory.join("errors.txt")).unwrap(), "-q is not a valid option for set\n");
+    assert!(std::fs::read_to_string(directory.join("options.txt")).unwrap().contains("pipefail"));
+    assert_eq!(std::fs::read_to_string(directory.join("upper.txt")).unwrap(), "B\n");
+}
+
+#[test]
+fn shell_large_output_test() {
+    let directory = temp_dir("shell_large_output");
+    let programs = PathBuf::from(env!("CARGO_BIN_EXE_cat")).parent().unwrap().to_path_buf();
+    let text = "0123456789abcdef\n".repeat(96 * 1024);
+    std::fs::write(directory.join("big.txt"), &text).unwrap();
+    let mut shell = Shell::builder().cwd(&directory).programs_dir(&programs).builtin(Upper).build();
+
+    assert_eq!(shell.run("cat big.txt").stdout.len(), text.len());
+    assert_eq!(shell.run("cat big.txt | upper").stdout_lossy(), text.to_uppercase());
+    assert_eq!(shell.run("cat big.txt | upper | upper > copy.txt ; echo done").stdout_lossy(), "done\n");
+    assert_eq!(std::fs::read_to_string(directory.join("copy.txt")).unwrap(), text.to_uppercase());
+}
+
+#[cfg(unix)]
+fn write_program(directory: &Path, name: &str, output: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    let path = directory.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\necho {}\n", output)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_pipeline_stage_test() {
+    let directory = temp_dir("shell_pipeline_stage");
+    write_program(&directory, "count", "$(wc -c)");
+    write_program(&directory, "swallow", "$(cat >/dev/null)");
+    let mut shell = Shell::builder().cwd(&directory).programs_dir(".").build();
+
+    assert_eq!(shell.run("cd nested | swallow ; pwd").stdout_lossy(), format!("\n{}\n", directory.display()));
+    assert_eq!(shell.run("x=1 | true ; echo [$x]").stdout_lossy(), "[]\n");
+    assert_eq!(shell.run("f() { y=2 ; echo f ; } ; f | swallow ; echo [$y]").stdout_lossy(), "\n[]\n");
+    assert_eq!(shell.run("echo hi | count").stdout_lossy(), "3\n");
+    assert_eq!(shell.run("printf abc | count ; pwd -P | count").stdout_lossy(), format!("3\n{}\n", directory.display().to_string().len() + 1));
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_compound_pipeline_stage_test() {
+    let directory = temp_dir("shell_compound_pipeline_stage");
+    write_program(&directory, "lines", "$(wc -l)");
+    let mut shell = Shell::builder().cwd(&directory).programs_dir(".").build();
+
+    assert_eq!(shell.run("for i in 1 2 3; do printf \"%s\\n\" $i; done | lines").stdout_lossy(), "3\n");
+    assert_eq!(shell.run("if true; then echo a; echo b; fi | lines").stdout_lossy(), "2\n");
+    assert_eq!(shell.run("case x in x) echo a; echo b; echo c;; esac | lines").stdout_lossy(), "3\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_streaming_test() {
+    let directory = temp_dir("shell_streaming");
+    write_program(&directory, "slow", "first ; sleep 1 ; echo second >&2");
+    let start = std::time::Instant::now();
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let (stdout, stderr) = (output.clone(), errors.clone());
+    let mut shell = Shell::builder()
+        .cwd(&directory)
+        .programs_dir(".")
+        .on_stdout(move |bytes| stdout.borrow_mut().push((String::from_utf8_lossy(bytes).into_owned(), start.elapsed())))
+        .on_stderr(move |bytes| stderr.borrow_mut().extend_from_slice(bytes))
+        .build();
+
+    let result = shell.run("echo before ; slow ; echo after");
+    let elapsed = start.elapsed();
+    let arrived = |text: &str| output.borrow().iter().find(|(chunk, _)| chunk.contains(text)).map(|(_, time)| *time).unwrap();
+    assert_eq!(result.stdout_lossy(), "before\nfirst\nafter\n");
+    assert_eq!(output.borrow().iter().map(|(chunk, _)| chunk.as_str()).collect::<String>(), "before\nfirst\nafter\n");
+    assert_eq!(*errors.borrow(), b"second\n".to_vec());
+    assert!(elapsed - arrived("first") >= std::time::Duration::from_millis(500));
+    assert!(arrived("after") - arrived("first") >= std::time::Duration::from_millis(500));
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_lossy_words_test() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let directory = temp_dir("shell_lossy_words");
+    std::fs::write(directory.join("valid.txt"), "").unwrap();
+    std::fs::write(directory.join(std::ffi::OsStr::from_bytes(b"invalid\xff.txt")), "").unwrap();
+    let mut shell = Shell::builder().cwd(&directory).build();
+
+    assert_eq!(shell.run("echo *.txt").stdout_lossy(), "valid.txt\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_programs_dirs_test() {
+    use shell::utils::{find_program, join_program_dirs};
+
+    let first = temp_dir("shell_programs_first");
+    let second = temp_dir("shell_programs_second");
+    write_program(&first, "both", "first");
+    write_program(&second, "both", "second");
+    write_program(&second, "only", "second only");
+
+    let dirs = join_program_dirs(&[first.clone(), second.clone()]);
+    assert_eq!(find_program(&dirs, "both"), Some(first.join("both")));
+    assert_eq!(find_program(&dirs, "only"), Some(second.join("only")));
+    assert_eq!(find_program(&dirs, "missing"), None);
+
+    let mut shell = Shell::builder().cwd(&first).programs_dir(".").programs_dir(&second).build();
+    assert_eq!(shell.run("both ; only").stdout_lossy(), "first\nsecond only\n");
+    assert_eq!(shell.run("type only").stdout_lossy(), format!("only is {}\n", second.join("only").display()));
+
+    let result = shell.run("missing");
+    assert_eq!((result.status, result.stderr_lossy()), (1, "Command not found: missing\n".to_string()));
+}
+
+#[test]
+fn shell_help_test() {
+    let mut shell = Shell::builder().clear_env().builtin(Upper).build();
+
+    let listing = shell.run("help").stdout_lossy();
+    assert!(listing.contains("    pipelines      Connect commands with |, &&, ||, ; and !\n"));
+    assert!(listing.contains("    cd [-L|-P] [dir]\n    clear [-x]\n"));
+    assert!(listing.contains("    upper\n"));
+
+    assert_eq!(shell.run("help upper").stdout_lossy(), "upper\n    Convert the standard input to upper case.\n");
+    assert!(shell.run("help redirects").stdout_lossy().starts_with("cmd < file\n    Read the standard input from file.\n"));
+    assert!(shell.run("help branching loops").stdout_lossy().contains("fi\n"));
+    assert_eq!(shell.run("help if").stdout_lossy(), shell.run("help branching").stdout_lossy());
+    assert_eq!(shell.run("help while done").stdout_lossy(), shell.run("help loops loops").stdout_lossy());
+    assert!(shell.run("help case function").stdout_lossy().contains("esac\n"));
+
+    let result = shell.run("help nothing");
+    assert_eq!((result.status, result.stderr_lossy()), (1, "No help topics match nothing\n".to_string()));
+}